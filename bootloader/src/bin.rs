@@ -9,6 +9,7 @@ use rp2040_hal::{pac, rom_data::reset_to_usb_boot, Sio};
 
 #[cfg(feature = "binaryinfo")]
 pub mod binary_info;
+mod dfu;
 
 extern "C" {
     static __bootloader_application_start: u32;
@@ -57,6 +58,10 @@ fn main() -> ! {
         check_bootloader();
     }
 
+    if unsafe { dfu::apply_pending_update() } {
+        cortex_m::peripheral::SCB::sys_reset();
+    }
+
     unsafe {
         let p = cortex_m::Peripherals::steal();
         let start = FLASH_BASE as u32 + &__bootloader_application_start as *const u32 as u32;
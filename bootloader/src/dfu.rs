@@ -0,0 +1,156 @@
+//! Applying firmware updates staged by the application
+//!
+//! The application writes a new image into the DFU region, after a sector
+//! holding a header, and reboots. If we find a valid header we copy the image
+//! over the application region and erase the header. Losing power part way
+//! through is fine, the header is still there and the copy starts again.
+
+use rp2040_hal::rom_data;
+
+use crate::FLASH_BASE;
+
+extern "C" {
+    static __bootloader_application_start: u32;
+    static __bootloader_dfu_start: u32;
+    static __bootloader_dfu_end: u32;
+}
+
+const SECTOR: u32 = 4096;
+
+/// Must match the firmware
+const HEADER_MAGIC: u32 = 0x4446_5521;
+
+const BLOCK_SIZE: u32 = 1 << 16;
+const BLOCK_ERASE_CMD: u8 = 0xd8;
+
+struct Header {
+    len: u32,
+    crc: u32,
+}
+
+unsafe fn read_word(offset: u32) -> u32 {
+    core::ptr::read_volatile((FLASH_BASE as u32 + offset) as *const u32)
+}
+
+unsafe fn read_header(dfu_start: u32, dfu_end: u32) -> Option<Header> {
+    if read_word(dfu_start) != HEADER_MAGIC {
+        return None;
+    }
+
+    let len = read_word(dfu_start + 4);
+    if len == 0 || dfu_start + SECTOR + len > dfu_end {
+        return None;
+    }
+
+    Some(Header {
+        len,
+        crc: read_word(dfu_start + 8),
+    })
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &b in data {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
+
+/// ROM flash routines, these have to be looked up before xip is disabled as
+/// the lookup lives in flash
+struct FlashFns {
+    connect_internal_flash: usize,
+    flash_exit_xip: usize,
+    flash_range_erase: usize,
+    flash_range_program: usize,
+    flash_flush_cache: usize,
+    flash_enter_cmd_xip: usize,
+}
+
+impl FlashFns {
+    fn lookup() -> Self {
+        Self {
+            connect_internal_flash: rom_data::connect_internal_flash::ptr() as usize,
+            flash_exit_xip: rom_data::flash_exit_xip::ptr() as usize,
+            flash_range_erase: rom_data::flash_range_erase::ptr() as usize,
+            flash_range_program: rom_data::flash_range_program::ptr() as usize,
+            flash_flush_cache: rom_data::flash_flush_cache::ptr() as usize,
+            flash_enter_cmd_xip: rom_data::flash_enter_cmd_xip::ptr() as usize,
+        }
+    }
+}
+
+/// Erase a sector, and if `data` is not null program it with a sector's worth
+/// of `data`
+///
+/// This runs with xip disabled so must live in ram and can't call anything
+/// that lives in flash.
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+unsafe fn flash_sector(fns: &FlashFns, offset: u32, data: *const u8) {
+    let connect_internal_flash: extern "C" fn() = core::mem::transmute(fns.connect_internal_flash);
+    let flash_exit_xip: extern "C" fn() = core::mem::transmute(fns.flash_exit_xip);
+    let flash_range_erase: extern "C" fn(u32, usize, u32, u8) =
+        core::mem::transmute(fns.flash_range_erase);
+    let flash_range_program: extern "C" fn(u32, *const u8, usize) =
+        core::mem::transmute(fns.flash_range_program);
+    let flash_flush_cache: extern "C" fn() = core::mem::transmute(fns.flash_flush_cache);
+    let flash_enter_cmd_xip: extern "C" fn() = core::mem::transmute(fns.flash_enter_cmd_xip);
+
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    connect_internal_flash();
+    flash_exit_xip();
+    flash_range_erase(offset, SECTOR as usize, BLOCK_SIZE, BLOCK_ERASE_CMD);
+    if !data.is_null() {
+        flash_range_program(offset, data, SECTOR as usize);
+    }
+    flash_flush_cache();
+    flash_enter_cmd_xip();
+
+    core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+/// Copy a staged update over the application, if there is one
+///
+/// Returns true if an update was applied, in which case the chip should be
+/// reset so that boot2 can set xip back up properly.
+pub unsafe fn apply_pending_update() -> bool {
+    let app_start = &__bootloader_application_start as *const u32 as u32;
+    let dfu_start = &__bootloader_dfu_start as *const u32 as u32;
+    let dfu_end = &__bootloader_dfu_end as *const u32 as u32;
+
+    let Some(header) = read_header(dfu_start, dfu_end) else {
+        return false;
+    };
+
+    let fns = FlashFns::lookup();
+    let image = (FLASH_BASE as u32 + dfu_start + SECTOR) as *const u8;
+
+    // the application checks this before committing, but we're about to
+    // overwrite it so it's worth being sure
+    if crc32(core::slice::from_raw_parts(image, header.len as usize)) != header.crc {
+        flash_sector(&fns, dfu_start, core::ptr::null());
+        return false;
+    }
+
+    let mut buf = [0xffu8; SECTOR as usize];
+
+    for offset in (0..header.len).step_by(SECTOR as usize) {
+        let n = (header.len - offset).min(SECTOR) as usize;
+
+        buf.fill(0xff);
+        core::ptr::copy_nonoverlapping(image.add(offset as usize), buf.as_mut_ptr(), n);
+
+        flash_sector(&fns, app_start + offset, buf.as_ptr());
+    }
+
+    flash_sector(&fns, dfu_start, core::ptr::null());
+
+    true
+}
@@ -2,10 +2,14 @@ MEMORY {
     BOOT2  : ORIGIN = 0x10000000, LENGTH = 0x100
     FLASH  : ORIGIN = 0x10007000, LENGTH = 1024K - 0x7000
     CONFIG : ORIGIN = ORIGIN(FLASH) + LENGTH(FLASH), LENGTH = 256K
+    /* firmware updates are staged here for the bootloader to copy over FLASH */
+    DFU    : ORIGIN = ORIGIN(CONFIG) + LENGTH(CONFIG), LENGTH = 768K
     RAM    : ORIGIN = 0x20000000, LENGTH = 256K
 }
 
 __config_start = ORIGIN(CONFIG) - ORIGIN(BOOT2);
+__dfu_start = ORIGIN(DFU) - ORIGIN(BOOT2);
+__dfu_end = ORIGIN(DFU) + LENGTH(DFU) - ORIGIN(BOOT2);
 
 SECTIONS {
   .config (NOLOAD) : ALIGN(4)
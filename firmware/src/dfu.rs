@@ -0,0 +1,237 @@
+//! Receiving new firmware images from the host
+//!
+//! Images are streamed into the DFU region of flash, after a sector reserved
+//! for a header. Once the whole image is in and its crc checks out the header
+//! is written and we reboot, the bootloader then copies the image over the
+//! application and erases the header.
+//!
+//! Updates for the other side are relayed to it by the usual host message
+//! routing, so each side only ever deals with its own image.
+
+use embassy_executor::Spawner;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use shared::{
+    device_to_host::{DeviceToHostMsg, FirmwareUpdateError, FirmwareUpdateStatus},
+    host_to_device::FirmwareUpdate,
+};
+
+use crate::messages::{distributors::MessageProvenance, reliable_msg, send_to_host};
+#[allow(unused_imports)]
+use crate::utils::log;
+
+static UPDATES: Channel<ThreadModeRawMutex, FirmwareUpdate, 2> = Channel::new();
+
+/// Queue up a step of a firmware update
+///
+/// Flash operations are slow, so they happen in their own task rather than
+/// holding up whoever delivered the message.
+pub async fn handle(update: FirmwareUpdate) {
+    UPDATES.send(update).await;
+}
+
+pub fn init(spawner: &Spawner) {
+    spawner.must_spawn(dfu_task());
+}
+
+async fn reply(status: FirmwareUpdateStatus) {
+    send_to_host(
+        reliable_msg(DeviceToHostMsg::FirmwareUpdate(status)),
+        MessageProvenance::Origin,
+    )
+    .await;
+}
+
+#[cfg(not(feature = "bootloader"))]
+#[embassy_executor::task]
+async fn dfu_task() {
+    loop {
+        let _ = UPDATES.receive().await;
+
+        reply(FirmwareUpdateStatus::Failed(
+            FirmwareUpdateError::Unsupported,
+        ))
+        .await;
+    }
+}
+
+#[cfg(feature = "bootloader")]
+#[embassy_executor::task]
+async fn dfu_task() {
+    let mut state = None;
+
+    loop {
+        let status = match staging::step(&mut state, UPDATES.receive().await).await {
+            Ok(status) => status,
+            Err(e) => {
+                log::warn!("Firmware update failed: {:?}", e);
+                state = None;
+                FirmwareUpdateStatus::Failed(e)
+            }
+        };
+
+        let committed = status == FirmwareUpdateStatus::Committed;
+
+        reply(status).await;
+
+        if committed {
+            log::info!("Rebooting into the new firmware");
+
+            // give the reply a moment to make it out
+            embassy_time::Timer::after_millis(200).await;
+            cortex_m::peripheral::SCB::sys_reset();
+        }
+    }
+}
+
+#[cfg(feature = "bootloader")]
+mod staging {
+    use shared::{
+        device_to_host::{FirmwareUpdateError, FirmwareUpdateStatus},
+        host_to_device::FirmwareUpdate,
+    };
+
+    use crate::{flash, utils::log};
+
+    extern "C" {
+        // u32 as align is 4
+        static __dfu_start: u32;
+        static __dfu_end: u32;
+    }
+
+    const SECTOR: u32 = 4096;
+
+    /// Must match the bootloader
+    const HEADER_MAGIC: u32 = 0x4446_5521;
+
+    pub struct InProgress {
+        len: u32,
+        crc: u32,
+        /// Everything before this has been written
+        written: u32,
+        /// Everything before this has been erased
+        erased: u32,
+    }
+
+    fn dfu_start() -> u32 {
+        unsafe { &__dfu_start as *const u32 as u32 }
+    }
+
+    fn dfu_end() -> u32 {
+        unsafe { &__dfu_end as *const u32 as u32 }
+    }
+
+    fn image_start() -> u32 {
+        dfu_start() + SECTOR
+    }
+
+    pub async fn step(
+        state: &mut Option<InProgress>,
+        update: FirmwareUpdate,
+    ) -> Result<FirmwareUpdateStatus, FirmwareUpdateError> {
+        match update {
+            FirmwareUpdate::Begin { len, crc } => {
+                *state = None;
+
+                let end = image_start()
+                    .checked_add(len)
+                    .ok_or(FirmwareUpdateError::TooLarge)?;
+
+                if end > dfu_end() {
+                    return Err(FirmwareUpdateError::TooLarge);
+                }
+
+                log::info!("Starting firmware update of {} bytes", len);
+
+                // clear out any previous header so a half written image can
+                // never be applied
+                flash::erase_raw(dfu_start(), image_start())
+                    .await
+                    .ok_or(FirmwareUpdateError::Flash)?;
+
+                *state = Some(InProgress {
+                    len,
+                    crc,
+                    written: 0,
+                    erased: 0,
+                });
+
+                Ok(FirmwareUpdateStatus::Ready)
+            }
+            FirmwareUpdate::Chunk { offset, data } => {
+                let s = state.as_mut().ok_or(FirmwareUpdateError::NotStarted)?;
+                let end = offset
+                    .checked_add(data.len() as u32)
+                    .ok_or(FirmwareUpdateError::TooLarge)?;
+
+                if end <= s.written {
+                    // a resend of something we already have
+                    return Ok(FirmwareUpdateStatus::Written { upto: s.written });
+                }
+
+                if offset != s.written {
+                    return Err(FirmwareUpdateError::OutOfOrder {
+                        expected: s.written,
+                    });
+                }
+
+                if end > s.len {
+                    return Err(FirmwareUpdateError::TooLarge);
+                }
+
+                // erase sectors as we reach them, rather than stalling the
+                // whole chip for ages up front
+                if end > s.erased {
+                    let to = end.next_multiple_of(SECTOR);
+                    flash::erase_raw(image_start() + s.erased, image_start() + to)
+                        .await
+                        .ok_or(FirmwareUpdateError::Flash)?;
+                    s.erased = to;
+                }
+
+                flash::write_raw(image_start() + offset, &data)
+                    .await
+                    .ok_or(FirmwareUpdateError::Flash)?;
+                s.written = end;
+
+                Ok(FirmwareUpdateStatus::Written { upto: s.written })
+            }
+            FirmwareUpdate::Commit => {
+                let s = state.as_ref().ok_or(FirmwareUpdateError::NotStarted)?;
+
+                if s.written != s.len {
+                    return Err(FirmwareUpdateError::Incomplete);
+                }
+
+                // check what actually ended up in flash, not what we were sent
+                let mut hasher = crc32fast::Hasher::new();
+                let mut buf = [0u8; 256];
+                let mut offset = 0;
+                while offset < s.len {
+                    let n = (s.len - offset).min(buf.len() as u32) as usize;
+                    flash::read_raw(image_start() + offset, &mut buf[..n])
+                        .await
+                        .ok_or(FirmwareUpdateError::Flash)?;
+                    hasher.update(&buf[..n]);
+                    offset += n as u32;
+                }
+
+                if hasher.finalize() != s.crc {
+                    return Err(FirmwareUpdateError::BadCrc);
+                }
+
+                let mut header = [0u8; 12];
+                header[0..4].copy_from_slice(&HEADER_MAGIC.to_le_bytes());
+                header[4..8].copy_from_slice(&s.len.to_le_bytes());
+                header[8..12].copy_from_slice(&s.crc.to_le_bytes());
+
+                flash::write_raw(dfu_start(), &header)
+                    .await
+                    .ok_or(FirmwareUpdateError::Flash)?;
+
+                *state = None;
+
+                Ok(FirmwareUpdateStatus::Committed)
+            }
+        }
+    }
+}
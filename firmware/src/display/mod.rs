@@ -2,6 +2,7 @@ use core::sync::atomic::{AtomicBool, AtomicUsize};
 
 use alloc::{boxed::Box, rc::Rc};
 use embassy_executor::Spawner;
+use embassy_futures::select::select;
 use embassy_rp::{
     multicore::{spawn_core1, Stack},
     peripherals::{CORE1, PIN_11, PIN_12, PIN_13, PIN_22, PIN_23, PWM_SLICE6, SPI0},
@@ -11,7 +12,11 @@ use embassy_time::{Duration, Timer};
 use embedded_hal_bus::spi::ExclusiveDevice;
 use slint::platform::software_renderer::Rgb565Pixel;
 
-use crate::metrics::{self, Metrics, METRIC_UPDATES};
+use crate::{
+    idle::{self, IDLE_CHANGES},
+    metrics::{self, Metrics, METRIC_UPDATES},
+    power::{self, POWER_CHANGES},
+};
 
 use self::{backend::PicoBackend, draw_buffer::DrawBuffer};
 
@@ -64,6 +69,7 @@ fn run(spi: SPI0, clk: PIN_22, mosi: PIN_23, cs: PIN_12, dc: PIN_11) -> ! {
             let window = Rc::clone(&window);
             move || {
                 window.set_keypresses(KEYS_PRESSED.load(portable_atomic::Ordering::Relaxed) as i32);
+                window.set_host_os(crate::host_os::name().into());
                 window.set_ticks(
                     crate::utils::executor_metrics::WAKEUPS.load(portable_atomic::Ordering::Relaxed)
                         as i32,
@@ -89,46 +95,56 @@ fn run(spi: SPI0, clk: PIN_22, mosi: PIN_23, cs: PIN_12, dc: PIN_11) -> ! {
 static KEYS_PRESSED: AtomicUsize = AtomicUsize::new(0);
 
 #[embassy_executor::task]
-async fn metrics_updater(bl: PIN_13, pwm: PWM_SLICE6) {
+async fn metrics_updater() {
     let mut sub = METRIC_UPDATES.subscriber().unwrap();
+
+    metrics::request_sync().await;
+
+    loop {
+        let Metrics { keys_pressed } = sub.next_message_pure().await;
+
+        KEYS_PRESSED.store(keys_pressed.0, portable_atomic::Ordering::Release);
+    }
+}
+
+/// Fades the backlight out when the keyboard goes idle or the host suspends,
+/// and back in afterwards
+#[embassy_executor::task]
+async fn backlight(bl: PIN_13, pwm: PWM_SLICE6) {
+    let mut idle_sub = IDLE_CHANGES.subscriber().unwrap();
+    let mut power_sub = POWER_CHANGES.subscriber().unwrap();
     let mut pwm_cfg = embassy_rp::pwm::Config::default();
     pwm_cfg.top = 256;
     pwm_cfg.compare_b = 256;
     let mut bl = embassy_rp::pwm::Pwm::new_output_b(pwm, bl, pwm_cfg.clone());
 
-    metrics::request_sync().await;
+    let mut off = false;
 
     loop {
-        let Metrics { keys_pressed } = match embassy_time::with_timeout(
-            Duration::from_secs(30),
-            sub.next_message_pure(),
-        )
-        .await
-        {
-            Ok(m) => m,
-            Err(_e) => {
-                for n in (0..=256).rev() {
-                    pwm_cfg.compare_b = n;
-                    bl.set_config(&pwm_cfg);
-                    Timer::after(Duration::from_hz(256)).await;
-                }
-                DISPLAY_OFF.store(true, portable_atomic::Ordering::Relaxed);
-
-                let r = sub.next_message_pure().await;
-
-                DISPLAY_OFF.store(false, portable_atomic::Ordering::Relaxed);
-
-                for n in 0..=256 {
-                    pwm_cfg.compare_b = n;
-                    bl.set_config(&pwm_cfg);
-                    Timer::after(Duration::from_hz(256)).await;
-                }
-
-                r
+        select(idle_sub.next_message_pure(), power_sub.next_message_pure()).await;
+
+        let should_be_off = idle::is_idle() || power::is_suspended();
+        if should_be_off == off {
+            continue;
+        }
+        off = should_be_off;
+
+        if off {
+            for n in (0..=256).rev() {
+                pwm_cfg.compare_b = n;
+                bl.set_config(&pwm_cfg);
+                Timer::after(Duration::from_hz(256)).await;
             }
-        };
-
-        KEYS_PRESSED.store(keys_pressed.0, portable_atomic::Ordering::Release);
+            DISPLAY_OFF.store(true, portable_atomic::Ordering::Relaxed);
+        } else {
+            DISPLAY_OFF.store(false, portable_atomic::Ordering::Relaxed);
+
+            for n in 0..=256 {
+                pwm_cfg.compare_b = n;
+                bl.set_config(&pwm_cfg);
+                Timer::after(Duration::from_hz(256)).await;
+            }
+        }
     }
 }
 
@@ -145,7 +161,8 @@ pub fn init(
     bl: PIN_13,
     pwm: PWM_SLICE6,
 ) {
-    spawner.must_spawn(metrics_updater(bl, pwm));
+    spawner.must_spawn(metrics_updater());
+    spawner.must_spawn(backlight(bl, pwm));
 
     spawn_core1(
         core1,
@@ -1,25 +1,40 @@
 use core::any::TypeId;
+use core::cell::RefCell;
 
 use ekv::flash::{self, PageID};
 use ekv::{config, Database};
+use embassy_executor::Spawner;
 use embassy_rp::flash::Flash;
 use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
+use embassy_sync::blocking_mutex::Mutex as BlockingMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
 use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
 use once_cell::sync::OnceCell;
 use rand::Rng;
 
 use crate::rng::MyRng;
+use crate::utils::log;
 
-static DB: OnceCell<
-    Database<
-        DbFlash<Flash<embassy_rp::peripherals::FLASH, embassy_rp::flash::Async, FLASH_SIZE>>,
-        ThreadModeRawMutex,
-    >,
-> = OnceCell::new();
+type RawFlash = Flash<embassy_rp::peripherals::FLASH, embassy_rp::flash::Async, FLASH_SIZE>;
+
+/// The flash is shared between the config database and anything that needs to
+/// write outside of it (firmware updates)
+static FLASH: OnceCell<Mutex<ThreadModeRawMutex, RawFlash>> = OnceCell::new();
+
+static DB: OnceCell<Database<DbFlash<RawFlash>, ThreadModeRawMutex>> = OnceCell::new();
+
+pub async fn init(
+    spawner: &Spawner,
+    flash: embassy_rp::peripherals::FLASH,
+    dma: embassy_rp::dma::AnyChannel,
+) {
+    FLASH.set(Mutex::new(Flash::new(flash, dma))).ok().unwrap();
+
+    spawner.must_spawn(saver());
 
-pub async fn init(flash: embassy_rp::peripherals::FLASH, dma: embassy_rp::dma::AnyChannel) {
     let flash = DbFlash {
-        flash: Flash::new(flash, dma),
+        flash: FLASH.get().unwrap(),
         start: unsafe { &__config_start as *const u32 as usize },
     };
     let mut cfg = ekv::Config::default();
@@ -33,41 +48,203 @@ pub async fn init(flash: embassy_rp::peripherals::FLASH, dma: embassy_rp::dma::A
     DB.set(db).ok().unwrap();
 }
 
-pub async fn set<T: core::any::Any + serde::Serialize>(value: &T) -> Option<()> {
-    let mut buf = [0u8; ekv::config::MAX_VALUE_SIZE];
-    let buf = postcard::to_slice(value, &mut buf).ok()?;
-    let mut tx = DB.get().unwrap().write_transaction().await;
+const KEY_LEN: usize = core::mem::size_of::<TypeId>();
+
+type Key = [u8; KEY_LEN];
 
+fn key_of<T: core::any::Any>() -> Key {
     // convert the typeid of the key to a byte array
-    let key = unsafe {
-        core::mem::transmute::<_, [u8; core::mem::size_of::<TypeId>()]>(TypeId::of::<T>())
-    };
+    unsafe { core::mem::transmute::<_, Key>(TypeId::of::<T>()) }
+}
 
-    tx.write(&key, &buf).await.ok()?;
+async fn write(key: &Key, value: &[u8]) -> Option<()> {
+    let mut tx = DB.get()?.write_transaction().await;
+
+    tx.write(key, value).await.ok()?;
     tx.commit().await.ok()?;
 
     Some(())
 }
 
+pub async fn set<T: core::any::Any + serde::Serialize>(value: &T) -> Option<()> {
+    let mut buf = [0u8; ekv::config::MAX_VALUE_SIZE];
+    let buf = postcard::to_slice(value, &mut buf).ok()?;
+
+    write(&key_of::<T>(), buf).await
+}
+
+/// Store a value in the background, replacing any of the same type still
+/// waiting to be written
+///
+/// Writing to flash is slow, this is for callers that shouldn't be held up by
+/// it. Only the latest value of each type is kept, so settings that change
+/// often don't queue up writes.
+pub fn save_later<T: core::any::Any + serde::Serialize>(value: &T) {
+    let queued = PENDING.lock(|p| p.borrow_mut().push(key_of::<T>(), value));
+
+    if queued.is_none() {
+        log::warn!(
+            "No room to queue a {} for saving",
+            core::any::type_name::<T>()
+        );
+        return;
+    }
+
+    SAVE_PENDING.signal(());
+}
+
 pub async fn get<T: core::any::Any + serde::de::DeserializeOwned>() -> Option<T> {
     let mut buf = [0u8; ekv::config::MAX_VALUE_SIZE];
 
     let tx = DB.get().unwrap().read_transaction().await;
 
-    // convert the typeid of the key to a byte array
-    let key = unsafe {
-        core::mem::transmute::<_, [u8; core::mem::size_of::<TypeId>()]>(TypeId::of::<T>())
-    };
-
-    let len = tx.read(&key, &mut buf).await.ok()?;
+    let len = tx.read(&key_of::<T>(), &mut buf).await.ok()?;
 
     postcard::from_bytes(&buf[..len]).ok()
 }
 
+/// Room for a couple of the largest values waiting to be saved
+const PENDING_SIZE: usize = 2 * config::MAX_VALUE_SIZE;
+
+/// Each record is a key, the length of the value then the serialized value
+const HEADER_LEN: usize = KEY_LEN + 2;
+
+/// Values given to [`save_later`] that haven't been written yet, kept
+/// serialized so that one buffer does for every type
+struct Pending {
+    buf: [u8; PENDING_SIZE],
+    used: usize,
+}
+
+impl Pending {
+    fn record_len(&self, start: usize) -> usize {
+        let len = &self.buf[start + KEY_LEN..start + HEADER_LEN];
+        HEADER_LEN + u16::from_le_bytes([len[0], len[1]]) as usize
+    }
+
+    fn remove(&mut self, key: &Key) {
+        let mut start = 0;
+
+        while start < self.used {
+            let len = self.record_len(start);
+
+            if &self.buf[start..start + KEY_LEN] == key {
+                self.buf.copy_within(start + len..self.used, start);
+                self.used -= len;
+                return;
+            }
+
+            start += len;
+        }
+    }
+
+    fn push<T: serde::Serialize>(&mut self, key: Key, value: &T) -> Option<()> {
+        self.remove(&key);
+
+        let start = self.used;
+        let end = (start + HEADER_LEN + config::MAX_VALUE_SIZE).min(PENDING_SIZE);
+        let value_len = postcard::to_slice(value, self.buf.get_mut(start + HEADER_LEN..end)?)
+            .ok()?
+            .len();
+
+        self.buf[start..start + KEY_LEN].copy_from_slice(&key);
+        self.buf[start + KEY_LEN..start + HEADER_LEN]
+            .copy_from_slice(&(value_len as u16).to_le_bytes());
+        self.used += HEADER_LEN + value_len;
+
+        Some(())
+    }
+
+    /// Take the oldest record, copying its value into `value`
+    fn pop(&mut self, value: &mut [u8; config::MAX_VALUE_SIZE]) -> Option<(Key, usize)> {
+        if self.used == 0 {
+            return None;
+        }
+
+        let len = self.record_len(0);
+        let value_len = len - HEADER_LEN;
+        let mut key = [0; KEY_LEN];
+        key.copy_from_slice(&self.buf[..KEY_LEN]);
+        value[..value_len].copy_from_slice(&self.buf[HEADER_LEN..len]);
+
+        self.buf.copy_within(len..self.used, 0);
+        self.used -= len;
+
+        Some((key, value_len))
+    }
+}
+
+static PENDING: BlockingMutex<ThreadModeRawMutex, RefCell<Pending>> =
+    BlockingMutex::new(RefCell::new(Pending {
+        buf: [0; PENDING_SIZE],
+        used: 0,
+    }));
+
+static SAVE_PENDING: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+#[embassy_executor::task]
+async fn saver() {
+    let mut buf = [0u8; config::MAX_VALUE_SIZE];
+
+    loop {
+        SAVE_PENDING.wait().await;
+
+        while let Some((key, len)) = PENDING.lock(|p| p.borrow_mut().pop(&mut buf)) {
+            if write(&key, &buf[..len]).await.is_none() {
+                log::warn!("Failed to save a value to flash");
+            }
+        }
+    }
+}
+
+/// Erase a range of flash outside of the config database
+///
+/// Offsets are from the start of flash and must be sector aligned
+pub async fn erase_raw(from: u32, to: u32) -> Option<()> {
+    let mut flash = FLASH.get()?.lock().await;
+    flash.erase(from, to).await.ok()
+}
+
+/// Write to flash outside of the config database, the range must have been
+/// erased first
+pub async fn write_raw(offset: u32, data: &[u8]) -> Option<()> {
+    let mut flash = FLASH.get()?.lock().await;
+    let mut buf = AlignedBuf([0; RAW_BLOCK]);
+
+    for (idx, block) in data.chunks(RAW_BLOCK).enumerate() {
+        buf.0[..block.len()].copy_from_slice(block);
+        flash
+            .write(offset + (idx * RAW_BLOCK) as u32, &buf.0[..block.len()])
+            .await
+            .ok()?;
+    }
+
+    Some(())
+}
+
+/// Read from flash outside of the config database
+pub async fn read_raw(offset: u32, data: &mut [u8]) -> Option<()> {
+    let mut flash = FLASH.get()?.lock().await;
+    let mut buf = AlignedBuf([0; RAW_BLOCK]);
+
+    for (idx, block) in data.chunks_mut(RAW_BLOCK).enumerate() {
+        flash
+            .read(offset + (idx * RAW_BLOCK) as u32, &mut buf.0[..block.len()])
+            .await
+            .ok()?;
+        block.copy_from_slice(&buf.0[..block.len()]);
+    }
+
+    Some(())
+}
+
+const RAW_BLOCK: usize = 256;
+
+// size of the whole chip, offsets are all from the start of flash
 #[cfg(feature = "m2")]
-const FLASH_SIZE: usize = 256 * 1024;
+const FLASH_SIZE: usize = 2048 * 1024;
 #[cfg(not(feature = "m2"))]
-const FLASH_SIZE: usize = 8192 * 1024;
+const FLASH_SIZE: usize = 16384 * 1024;
 
 extern "C" {
     // u32 as align is 4
@@ -78,9 +255,9 @@ extern "C" {
 #[repr(C, align(4))]
 struct AlignedBuf<const N: usize>([u8; N]);
 
-struct DbFlash<T: NorFlash + ReadNorFlash> {
+struct DbFlash<T: NorFlash + ReadNorFlash + 'static> {
     start: usize,
-    flash: T,
+    flash: &'static Mutex<ThreadModeRawMutex, T>,
 }
 
 impl<T: NorFlash + ReadNorFlash> flash::Flash for DbFlash<T> {
@@ -92,6 +269,8 @@ impl<T: NorFlash + ReadNorFlash> flash::Flash for DbFlash<T> {
 
     async fn erase(&mut self, page_id: PageID) -> Result<(), <DbFlash<T> as flash::Flash>::Error> {
         self.flash
+            .lock()
+            .await
             .erase(
                 (self.start + page_id.index() * config::PAGE_SIZE) as u32,
                 (self.start + page_id.index() * config::PAGE_SIZE + config::PAGE_SIZE) as u32,
@@ -108,6 +287,8 @@ impl<T: NorFlash + ReadNorFlash> flash::Flash for DbFlash<T> {
         let address = self.start + page_id.index() * config::PAGE_SIZE + offset;
         let mut buf = AlignedBuf([0; config::PAGE_SIZE]);
         self.flash
+            .lock()
+            .await
             .read(address as u32, &mut buf.0[..data.len()])
             .await?;
         data.copy_from_slice(&buf.0[..data.len()]);
@@ -123,6 +304,10 @@ impl<T: NorFlash + ReadNorFlash> flash::Flash for DbFlash<T> {
         let address = self.start + page_id.index() * config::PAGE_SIZE + offset;
         let mut buf = AlignedBuf([0; config::PAGE_SIZE]);
         buf.0[..data.len()].copy_from_slice(data);
-        self.flash.write(address as u32, &buf.0[..data.len()]).await
+        self.flash
+            .lock()
+            .await
+            .write(address as u32, &buf.0[..data.len()])
+            .await
     }
 }
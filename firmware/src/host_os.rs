@@ -0,0 +1,100 @@
+//! Which OS the host is running
+//!
+//! It's guessed from how the host talks to us while usb comes up, which isn't
+//! always right, so it can be forced from a key or the host instead. That's
+//! remembered across reboots, and the primary side passes it on so that either
+//! side can take over.
+
+use embassy_os_guess::OS;
+use portable_atomic::{AtomicU8, Ordering};
+use serde::{Deserialize, Serialize};
+use shared::host_to_device::HostOs;
+
+use crate::{
+    flash, interboard,
+    messages::{device_to_device::DeviceToDevice, reliable_msg},
+    side,
+    usb::guessed_host_os,
+    utils::log,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+struct Override(Option<HostOs>);
+
+/// An atomic as the display reads it from the other core
+static OVERRIDE: AtomicU8 = AtomicU8::new(0);
+
+fn encode(os: Option<HostOs>) -> u8 {
+    match os {
+        None => 0,
+        Some(HostOs::Linux) => 1,
+        Some(HostOs::Mac) => 2,
+        Some(HostOs::Windows) => 3,
+    }
+}
+
+fn decode(n: u8) -> Option<HostOs> {
+    match n {
+        1 => Some(HostOs::Linux),
+        2 => Some(HostOs::Mac),
+        3 => Some(HostOs::Windows),
+        _ => None,
+    }
+}
+
+pub async fn init() {
+    if let Some(o) = flash::get::<Override>().await {
+        log::info!("Loaded host os override: {:?}", o);
+        OVERRIDE.store(encode(o.0), Ordering::Relaxed);
+    }
+}
+
+/// The OS the host has been forced to, if it has been
+pub fn overridden() -> Option<HostOs> {
+    decode(OVERRIDE.load(Ordering::Relaxed))
+}
+
+/// The OS to act as if the host is running, if we have any idea
+pub fn effective() -> Option<HostOs> {
+    overridden().or_else(|| {
+        guessed_host_os().map(|os| match os {
+            OS::Linux => HostOs::Linux,
+            OS::Windows => HostOs::Windows,
+            _ => HostOs::Mac,
+        })
+    })
+}
+
+pub fn name() -> &'static str {
+    match effective() {
+        Some(HostOs::Linux) => "Linux",
+        Some(HostOs::Mac) => "Mac",
+        Some(HostOs::Windows) => "Windows",
+        None => "Unknown",
+    }
+}
+
+fn apply(os: Option<HostOs>) {
+    OVERRIDE.store(encode(os), Ordering::Relaxed);
+
+    flash::save_later(&Override(os));
+}
+
+/// Force the host OS, or go back to guessing with `None`, passing it on to
+/// the other side if we're primary
+pub async fn set(os: Option<HostOs>) {
+    apply(os);
+
+    if side::is_primary() {
+        let msg = DeviceToDevice::SyncHostOs(os);
+        interboard::send_msg(reliable_msg(msg), 3).await;
+    }
+}
+
+/// The override as decided by the primary side
+pub fn synced(os: Option<HostOs>) {
+    if !side::is_primary() {
+        apply(os);
+    }
+}
@@ -0,0 +1,142 @@
+//! Turning the leds and display off when the keyboard isn't being used
+//!
+//! Key presses on either half and trackpad movement count as activity. The
+//! primary side decides when the keyboard has gone idle and tells the other
+//! side, either side wakes up straight away on activity it sees.
+
+use embassy_executor::Spawner;
+use embassy_futures::select::{select4, Either4};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pubsub::PubSubChannel};
+use embassy_time::{Duration, Instant};
+use portable_atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    event::Event,
+    flash,
+    interboard::{self, THIS_SIDE_MESSAGE_BUS},
+    keys::KEY_EVENTS,
+    messages::{device_to_device::DeviceToDevice, reliable_msg},
+    side,
+    utils::{log, Ticker},
+};
+
+const CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// Seconds without activity before going idle, zero to never go idle
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+struct IdleTimeout(u32);
+
+const DEFAULT_TIMEOUT: IdleTimeout = IdleTimeout(2 * 60);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub enum IdleState {
+    Active,
+    Idle,
+}
+
+/// Published to whenever this side goes idle or wakes up
+pub static IDLE_CHANGES: PubSubChannel<ThreadModeRawMutex, IdleState, 1, 4, 1> =
+    PubSubChannel::new();
+
+static IS_IDLE: AtomicBool = AtomicBool::new(false);
+static TIMEOUT_SECS: AtomicU32 = AtomicU32::new(DEFAULT_TIMEOUT.0);
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+static WOKEN: Event = Event::new();
+
+pub async fn init(spawner: &Spawner) {
+    if let Some(t) = flash::get::<IdleTimeout>().await {
+        log::info!("Loaded idle timeout: {:?}", t);
+        TIMEOUT_SECS.store(t.0, Ordering::Relaxed);
+    }
+
+    spawner.must_spawn(idle_task());
+}
+
+pub fn is_idle() -> bool {
+    IS_IDLE.load(Ordering::Relaxed)
+}
+
+fn set_idle(idle: bool) {
+    if IS_IDLE.swap(idle, Ordering::Relaxed) != idle {
+        log::info!("Idle? {}", idle);
+
+        let state = if idle {
+            IdleState::Idle
+        } else {
+            IdleState::Active
+        };
+        IDLE_CHANGES.immediate_publisher().publish_immediate(state);
+    }
+}
+
+/// Note that the keyboard is being used
+pub fn activity() {
+    LAST_ACTIVITY.store(Instant::now().as_ticks(), Ordering::Relaxed);
+
+    if is_idle() {
+        WOKEN.set();
+    }
+}
+
+/// Change how long the keyboard waits before going idle, zero to never go idle
+pub fn set_timeout(secs: u32) {
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+    flash::save_later(&IdleTimeout(secs));
+}
+
+fn timed_out() -> bool {
+    let secs = TIMEOUT_SECS.load(Ordering::Relaxed);
+    let last = Instant::from_ticks(LAST_ACTIVITY.load(Ordering::Relaxed));
+
+    secs != 0 && last.elapsed() > Duration::from_secs(secs as u64)
+}
+
+async fn announce(idle: bool) {
+    interboard::send_msg(reliable_msg(DeviceToDevice::SyncIdle(idle)), 3).await;
+}
+
+#[embassy_executor::task]
+async fn idle_task() {
+    let mut key_sub = KEY_EVENTS.subscriber().unwrap();
+    let mut sub = THIS_SIDE_MESSAGE_BUS.subscriber().unwrap();
+    let mut ticker = Ticker::every(CHECK_PERIOD);
+
+    activity();
+
+    loop {
+        match select4(
+            key_sub.next_message_pure(),
+            sub.next_message_pure(),
+            WOKEN.wait(),
+            ticker.next(),
+        )
+        .await
+        {
+            Either4::First(_) => activity(),
+            // trackpad movement from the other side
+            Either4::Second(DeviceToDevice::ForwardedToHostMouse(_)) => activity(),
+            Either4::Second(DeviceToDevice::SyncIdle(idle)) => {
+                if !side::is_primary() {
+                    set_idle(idle);
+                }
+            }
+            Either4::Second(_) => {}
+            Either4::Third(()) => {
+                set_idle(false);
+
+                if side::is_primary() {
+                    announce(false).await;
+                }
+            }
+            Either4::Fourth(()) => {
+                if side::is_primary() && !is_idle() && timed_out() {
+                    set_idle(true);
+                    announce(true).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+//! Decides which half is primary
+//!
+//! Each side periodically tells the other whether it has usb and whether it
+//! considers itself primary. The rules are:
+//!
+//! - while the link is down, a side is primary if it has usb
+//! - a side without usb is never primary
+//! - if only one side has usb, it is primary
+//! - if both have usb, whichever is already primary stays so, and if that
+//!   doesn't settle it the left side wins
+
+use embassy_executor::Spawner;
+use embassy_futures::select::{select4, Either4};
+use embassy_time::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    messages::{device_to_device::DeviceToDevice, unreliable_msg},
+    side::{self, Role},
+    utils::Ticker,
+};
+
+use super::{link::LinkState, THIS_SIDE_MESSAGE_BUS};
+
+const ANNOUNCE_PERIOD: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub struct RoleAnnouncement {
+    pub has_usb: bool,
+    pub primary: bool,
+}
+
+pub fn init(spawner: &Spawner) {
+    spawner.must_spawn(arbitration_task());
+}
+
+fn arbitrate(peer: Option<RoleAnnouncement>) -> Role {
+    let has_usb = side::this_side_has_usb();
+
+    let primary = match peer {
+        None => has_usb,
+        Some(peer) => match (has_usb, peer.has_usb) {
+            (false, _) => false,
+            (true, false) => true,
+            (true, true) => match (side::is_primary(), peer.primary) {
+                (true, false) => true,
+                (false, true) => false,
+                _ => side::get_side().is_left(),
+            },
+        },
+    };
+
+    if primary {
+        Role::Primary
+    } else {
+        Role::Secondary
+    }
+}
+
+fn announce() {
+    let announcement = RoleAnnouncement {
+        has_usb: side::this_side_has_usb(),
+        primary: side::is_primary(),
+    };
+
+    let _ = super::try_send_msg(
+        unreliable_msg(DeviceToDevice::AnnounceRole(announcement)),
+        2,
+    );
+}
+
+#[embassy_executor::task]
+async fn arbitration_task() {
+    let mut link_sub = super::link::LINK_STATE_CHANGES.subscriber().unwrap();
+    let mut sub = THIS_SIDE_MESSAGE_BUS.subscriber().unwrap();
+    let mut usb_sub = side::USB_CHANGES.subscriber().unwrap();
+    let mut ticker = Ticker::every(ANNOUNCE_PERIOD);
+
+    let mut peer = None;
+
+    loop {
+        // replying to every announcement would bounce them back and forth
+        // forever, so only announce periodically or when something changed
+        let mut should_announce = match select4(
+            ticker.next(),
+            sub.next_message_pure(),
+            link_sub.next_message_pure(),
+            usb_sub.next_message_pure(),
+        )
+        .await
+        {
+            Either4::First(()) => true,
+            Either4::Second(DeviceToDevice::AnnounceRole(announcement)) => {
+                peer = Some(announcement);
+                false
+            }
+            Either4::Second(_) => continue,
+            Either4::Third(LinkState::Up) => true,
+            Either4::Third(LinkState::Down) => {
+                peer = None;
+                false
+            }
+            // let the other side know straight away so it can take over
+            Either4::Fourth(_) => true,
+        };
+
+        let role = arbitrate(peer);
+        should_announce |= role != side::role();
+        side::set_role(role);
+
+        if should_announce {
+            announce();
+        }
+    }
+}
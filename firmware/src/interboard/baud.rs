@@ -0,0 +1,241 @@
+//! Negotiation of the interboard link speed
+//!
+//! Both sides boot at the slowest rate. Once the link has been clean for a
+//! while the primary side proposes the next rate up, the other side accepts
+//! and both switch over. If the error rate climbs the primary side proposes
+//! stepping back down, and if the link drops entirely both sides fall back to
+//! the slowest rate on their own.
+
+use embassy_executor::Spawner;
+use embassy_futures::select::{select3, Either3};
+use embassy_time::{Duration, Instant, Timer};
+use portable_atomic::Ordering;
+
+use crate::{
+    messages::{device_to_device::DeviceToDevice, reliable_msg, unreliable_msg},
+    side,
+    utils::{log, Ticker},
+};
+
+use super::{
+    link::{self, LinkState, LINK_STATS},
+    onewire::{self, BAUD_RATES},
+    THIS_SIDE_MESSAGE_BUS,
+};
+
+const CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// How long the error rate is measured over before deciding to change speed
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// How long to wait for the other side to accept a proposal
+const ACCEPT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long the accepting side waits before switching, so the acceptance can
+/// make it out at the old rate
+const SWITCH_DELAY: Duration = Duration::from_millis(20);
+
+/// If the link drops this soon after a switch the new rate is blamed for it
+const VERIFY_PERIOD: Duration = Duration::from_secs(3);
+
+/// How long a rate that failed is avoided for before being tried again
+const CEILING_RESET: Duration = Duration::from_secs(10 * 60);
+
+pub fn init(spawner: &Spawner) {
+    spawner.must_spawn(baud_task());
+}
+
+fn rate_index(rate: u32) -> usize {
+    BAUD_RATES.iter().position(|&r| r == rate).unwrap_or(0)
+}
+
+/// Counts of received and bad frames since the start of a window
+struct ErrorWindow {
+    started: Instant,
+    received: u32,
+    errors: u32,
+}
+
+impl ErrorWindow {
+    fn errors() -> u32 {
+        LINK_STATS
+            .csum_failures
+            .load(Ordering::Relaxed)
+            .wrapping_add(LINK_STATS.deser_errors.load(Ordering::Relaxed))
+            .wrapping_add(LINK_STATS.overfull.load(Ordering::Relaxed))
+    }
+
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            received: LINK_STATS.received.load(Ordering::Relaxed),
+            errors: Self::errors(),
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.started.elapsed() >= WINDOW
+    }
+
+    /// (received, errors) since the window started, the counters wrap
+    fn counts(&self) -> (u32, u32) {
+        (
+            LINK_STATS
+                .received
+                .load(Ordering::Relaxed)
+                .wrapping_sub(self.received),
+            Self::errors().wrapping_sub(self.errors),
+        )
+    }
+}
+
+/// State used when this side is the one driving the negotiation
+struct Negotiator {
+    /// Index of the fastest rate we're willing to try
+    ceiling: usize,
+    ceiling_lowered_at: Instant,
+    /// A proposal waiting to be accepted, and when it was sent
+    proposed: Option<(u32, Instant)>,
+    /// The last switch we made, and when
+    switched: Option<(usize, Instant)>,
+    window: ErrorWindow,
+}
+
+impl Negotiator {
+    fn new() -> Self {
+        Self {
+            ceiling: BAUD_RATES.len() - 1,
+            ceiling_lowered_at: Instant::now(),
+            proposed: None,
+            switched: None,
+            window: ErrorWindow::new(),
+        }
+    }
+
+    fn lower_ceiling(&mut self, below: usize) {
+        self.ceiling = below.saturating_sub(1);
+        self.ceiling_lowered_at = Instant::now();
+    }
+
+    fn propose(&mut self, idx: usize) {
+        let rate = BAUD_RATES[idx];
+        log::info!("Proposing interboard baud rate of {}", rate);
+
+        if super::try_send_msg(reliable_msg(DeviceToDevice::ProposeBaud(rate)), 1).is_ok() {
+            self.proposed = Some((rate, Instant::now()));
+        }
+    }
+
+    fn tick(&mut self) {
+        if let Some((_, at)) = self.proposed {
+            if at.elapsed() < ACCEPT_TIMEOUT {
+                return;
+            }
+            self.proposed = None;
+        }
+
+        if self.ceiling < BAUD_RATES.len() - 1 && self.ceiling_lowered_at.elapsed() > CEILING_RESET
+        {
+            self.ceiling += 1;
+            self.ceiling_lowered_at = Instant::now();
+        }
+
+        if !link::is_up() {
+            self.window = ErrorWindow::new();
+            return;
+        }
+
+        if !self.window.is_complete() {
+            return;
+        }
+
+        let (received, errors) = self.window.counts();
+        self.window = ErrorWindow::new();
+
+        let current = rate_index(onewire::current_baud());
+
+        if errors >= 5 && errors * 20 > received {
+            if current > 0 {
+                log::warn!(
+                    "Interboard link saw {} errors in {} messages, slowing down",
+                    errors,
+                    received
+                );
+                self.lower_ceiling(current);
+                self.propose(current - 1);
+            }
+        } else if errors * 100 <= received && current < self.ceiling {
+            self.propose(current + 1);
+        }
+    }
+
+    fn accepted(&mut self, rate: u32) {
+        if !matches!(self.proposed, Some((proposed, _)) if proposed == rate) {
+            return;
+        }
+
+        self.proposed = None;
+        self.switched = Some((rate_index(rate), Instant::now()));
+        self.window = ErrorWindow::new();
+        onewire::set_baud(rate);
+    }
+
+    fn link_down(&mut self) {
+        if let Some((idx, at)) = self.switched.take() {
+            if at.elapsed() < VERIFY_PERIOD && idx > 0 {
+                log::warn!(
+                    "Interboard link failed at {} baud, not trying it again for a while",
+                    BAUD_RATES[idx]
+                );
+                self.lower_ceiling(idx);
+            }
+        }
+
+        self.proposed = None;
+    }
+}
+
+#[embassy_executor::task]
+async fn baud_task() {
+    let mut link_sub = link::LINK_STATE_CHANGES.subscriber().unwrap();
+    let mut sub = THIS_SIDE_MESSAGE_BUS.subscriber().unwrap();
+    let mut ticker = Ticker::every(CHECK_PERIOD);
+
+    let mut negotiator = Negotiator::new();
+
+    loop {
+        match select3(
+            ticker.next(),
+            sub.next_message_pure(),
+            link_sub.next_message_pure(),
+        )
+        .await
+        {
+            Either3::First(()) => {
+                if side::is_primary() {
+                    negotiator.tick();
+                }
+            }
+            Either3::Second(DeviceToDevice::ProposeBaud(rate)) => {
+                if !BAUD_RATES.contains(&rate) {
+                    continue;
+                }
+
+                let _ = super::try_send_msg(unreliable_msg(DeviceToDevice::AcceptBaud(rate)), 0);
+                Timer::after(SWITCH_DELAY).await;
+                onewire::set_baud(rate);
+            }
+            Either3::Second(DeviceToDevice::AcceptBaud(rate)) => {
+                negotiator.accepted(rate);
+            }
+            Either3::Second(_) => {}
+            Either3::Third(LinkState::Down) => {
+                onewire::set_baud(BAUD_RATES[0]);
+                negotiator.link_down();
+            }
+            Either3::Third(LinkState::Up) => {
+                negotiator.window = ErrorWindow::new();
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+//! A clock both sides agree on
+//!
+//! Each side's clock starts from zero when it powers up, so the two can be any
+//! distance apart. Heartbeat pings carry the time they were sent and the pong
+//! says when the other side got the ping, which gives the offset between the
+//! clocks if the trip took as long each way. Messages can sit in the queue for
+//! a while, so out of the recent samples the one with the quickest round trip
+//! is trusted.
+//!
+//! The shared clock is the primary side's.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use embassy_time::Instant;
+use portable_atomic::{AtomicI64, Ordering};
+use serde::{Deserialize, Serialize};
+
+use crate::side;
+
+/// Samples to pick from, at the heartbeat rate this covers a few seconds
+const WINDOW: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    round_trip: u64,
+    offset: i64,
+}
+
+struct Samples {
+    samples: [Option<Sample>; WINDOW],
+    next: usize,
+}
+
+static SAMPLES: Mutex<ThreadModeRawMutex, RefCell<Samples>> = Mutex::new(RefCell::new(Samples {
+    samples: [None; WINDOW],
+    next: 0,
+}));
+
+/// The other side's clock minus ours, in ticks
+static OFFSET: AtomicI64 = AtomicI64::new(0);
+
+/// Our clock, for stamping pings and pongs with
+pub fn local_ticks() -> u64 {
+    Instant::now().as_ticks()
+}
+
+/// Note the reply to one of our pings, both times are in ticks: `ping_sent` on
+/// our clock and `received` on the other side's
+pub fn pong(ping_sent: u64, received: u64) {
+    let Some(round_trip) = local_ticks().checked_sub(ping_sent) else {
+        return;
+    };
+
+    let midpoint = ping_sent + round_trip / 2;
+    let sample = Sample {
+        round_trip,
+        offset: received as i64 - midpoint as i64,
+    };
+
+    let best = SAMPLES.lock(|s| {
+        let mut s = s.borrow_mut();
+        let next = s.next;
+        s.samples[next] = Some(sample);
+        s.next = (next + 1) % WINDOW;
+
+        s.samples
+            .iter()
+            .flatten()
+            .min_by_key(|s| s.round_trip)
+            .map(|s| s.offset)
+    });
+
+    if let Some(offset) = best {
+        OFFSET.store(offset, Ordering::Relaxed);
+    }
+}
+
+/// How far the shared clock is ahead of ours
+fn offset() -> i64 {
+    if side::is_primary() {
+        0
+    } else {
+        OFFSET.load(Ordering::Relaxed)
+    }
+}
+
+/// A point in time on the shared clock
+///
+/// Only for sending to the other side, convert to and from [`Instant`] at
+/// either end as the offset changes if the sides swap roles.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub struct SharedInstant(u64);
+
+impl SharedInstant {
+    pub fn from_local(instant: Instant) -> Self {
+        Self(instant.as_ticks().saturating_add_signed(offset()))
+    }
+
+    pub fn to_local(self) -> Instant {
+        Instant::from_ticks(self.0.saturating_add_signed(offset().saturating_neg()))
+    }
+}
@@ -0,0 +1,120 @@
+use embassy_executor::Spawner;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pubsub::PubSubChannel};
+use embassy_time::{with_timeout, Duration};
+use portable_atomic::{AtomicBool, Ordering};
+use shared::device_to_host::{DeviceToHostMsg, LinkMetrics};
+
+use crate::{
+    event::Event,
+    messages::{
+        self, device_to_device::DeviceToDevice, distributors::MessageProvenance,
+        transmissions::TransmissionStats, unreliable_msg,
+    },
+    utils::{log, Ticker},
+};
+
+/// How often we poke the other side so it knows we're still here
+const HEARTBEAT_PERIOD: Duration = Duration::from_millis(250);
+
+/// If nothing is heard from the other side for this long the link is
+/// considered down
+const LINK_TIMEOUT: Duration = Duration::from_millis(1000);
+
+const METRICS_PERIOD: Duration = Duration::from_secs(10);
+
+pub static LINK_STATS: TransmissionStats = TransmissionStats::new();
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub enum LinkState {
+    Up,
+    Down,
+}
+
+/// Published to whenever the link to the other side comes up or goes down
+pub static LINK_STATE_CHANGES: PubSubChannel<ThreadModeRawMutex, LinkState, 2, 8, 1> =
+    PubSubChannel::new();
+
+static LINK_UP: AtomicBool = AtomicBool::new(false);
+static ACTIVITY: Event = Event::new();
+
+pub fn init(spawner: &Spawner) {
+    spawner.must_spawn(heartbeat());
+    spawner.must_spawn(link_monitor());
+    spawner.must_spawn(metrics_reporter());
+}
+
+pub fn is_up() -> bool {
+    LINK_UP.load(Ordering::Relaxed)
+}
+
+/// Note that a valid message was received from the other side
+pub fn saw_activity() {
+    ACTIVITY.set();
+}
+
+pub fn metrics() -> LinkMetrics {
+    LinkMetrics {
+        link_up: is_up(),
+        baud: super::onewire::current_baud(),
+        received: LINK_STATS.received.load(Ordering::Relaxed),
+        retries: LINK_STATS.retries.load(Ordering::Relaxed),
+        csum_failures: LINK_STATS.csum_failures.load(Ordering::Relaxed),
+        deser_errors: LINK_STATS.deser_errors.load(Ordering::Relaxed),
+        overfull: LINK_STATS.overfull.load(Ordering::Relaxed),
+        gave_up: LINK_STATS.gave_up.load(Ordering::Relaxed),
+        dropped: super::channel::COMMANDS_TO_OTHER_SIDE.dropped(),
+    }
+}
+
+#[embassy_executor::task]
+async fn heartbeat() {
+    let mut ticker = Ticker::every(HEARTBEAT_PERIOD);
+
+    loop {
+        ticker.next().await;
+
+        // if the queue is full then there's plenty of traffic going to the
+        // other side already, no need to wait around
+        let _ = super::try_send_msg(
+            unreliable_msg(DeviceToDevice::Ping(super::clock::local_ticks())),
+            3,
+        );
+    }
+}
+
+#[embassy_executor::task]
+async fn link_monitor() {
+    let publisher = LINK_STATE_CHANGES.immediate_publisher();
+
+    loop {
+        let up = with_timeout(LINK_TIMEOUT, ACTIVITY.wait()).await.is_ok();
+
+        if LINK_UP.swap(up, Ordering::Relaxed) != up {
+            let state = if up { LinkState::Up } else { LinkState::Down };
+            log::info!("Interboard link is now {:?}", state);
+
+            if up {
+                super::channel::COMMANDS_TO_OTHER_SIDE.release_held();
+            } else {
+                // most of what's queued is stale now, it'll be resynced
+                // when the link comes back
+                super::channel::COMMANDS_TO_OTHER_SIDE.purge();
+            }
+
+            publisher.publish_immediate(state);
+        }
+    }
+}
+
+#[embassy_executor::task]
+async fn metrics_reporter() {
+    let mut ticker = Ticker::every(METRICS_PERIOD);
+
+    loop {
+        ticker.next().await;
+
+        let msg = DeviceToHostMsg::LinkMetrics(metrics());
+        messages::send_to_host(unreliable_msg(msg), MessageProvenance::Origin).await;
+    }
+}
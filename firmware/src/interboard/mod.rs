@@ -8,9 +8,14 @@ use embassy_rp::{
 use crate::messages::{device_to_device::DeviceToDevice, TransmittedMessage};
 
 pub use self::channel::THIS_SIDE_MESSAGE_BUS;
-use self::{channel::PrioritisedMessage, onewire::SM};
+use self::onewire::SM;
+pub mod arbitration;
+pub mod baud;
 pub mod channel;
+pub mod clock;
+pub mod link;
 pub mod onewire;
+pub mod queue;
 
 pub fn init(
     spawner: &Spawner,
@@ -22,16 +27,18 @@ pub fn init(
     onewire::init(spawner, common, tx_sm, rx_sm, pin);
 
     spawner.must_spawn(channel::eventer_task());
+    link::init(spawner);
+    baud::init(spawner);
+    arbitration::init(spawner);
 }
 
+/// Send a message to the other side, lower priorities go first
+///
+/// See [`queue`] for what happens when the queue is full or the link is down
 pub async fn send_msg(msg: TransmittedMessage<DeviceToDevice>, priority: u8) {
-    channel::COMMANDS_TO_OTHER_SIDE
-        .send(PrioritisedMessage { msg, priority })
-        .await;
+    channel::COMMANDS_TO_OTHER_SIDE.send(msg, priority).await;
 }
 
 pub fn try_send_msg(msg: TransmittedMessage<DeviceToDevice>, priority: u8) -> Result<(), ()> {
-    channel::COMMANDS_TO_OTHER_SIDE
-        .try_send(PrioritisedMessage { msg, priority })
-        .map_err(|_e| ())
+    channel::COMMANDS_TO_OTHER_SIDE.try_send(msg, priority)
 }
@@ -1,6 +1,6 @@
 use embassy_executor::Spawner;
 use embassy_futures::{
-    select::{self, select},
+    select::{self, select3},
     yield_now,
 };
 use embassy_rp::{
@@ -9,9 +9,10 @@ use embassy_rp::{
     pio::{Common, FifoJoin, Pin, PioPin, ShiftDirection, StateMachine},
     Peripheral,
 };
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pipe::Pipe};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pipe::Pipe, signal::Signal};
 use embassy_time::{Duration, Timer};
 use fixed::{traits::ToFixed, types::U56F8};
+use portable_atomic::{AtomicU32, Ordering};
 
 #[allow(unused_imports)]
 use crate::utils::log;
@@ -19,7 +20,27 @@ use crate::utils::log;
 pub static OTHER_SIDE_TX: Pipe<ThreadModeRawMutex, 32> = Pipe::new();
 pub static OTHER_SIDE_RX: Pipe<ThreadModeRawMutex, 32> = Pipe::new();
 
-pub const USART_SPEED: u64 = 460800;
+/// Speeds the link may run at, slowest first
+///
+/// Both sides always start at (and fall back to) the slowest speed
+pub const BAUD_RATES: [u32; 3] = [115200, 230400, 460800];
+
+static CURRENT_BAUD: AtomicU32 = AtomicU32::new(BAUD_RATES[0]);
+static BAUD_CHANGED: Signal<ThreadModeRawMutex, u32> = Signal::new();
+
+pub fn current_baud() -> u32 {
+    CURRENT_BAUD.load(Ordering::Relaxed)
+}
+
+/// Switch the link to a different speed
+///
+/// This takes effect once any in-progress transmission has finished
+pub fn set_baud(rate: u32) {
+    if CURRENT_BAUD.swap(rate, Ordering::Relaxed) != rate {
+        log::info!("Switching interboard link to {} baud", rate);
+        BAUD_CHANGED.signal(rate);
+    }
+}
 
 pub fn init(
     spawner: &Spawner,
@@ -43,7 +64,7 @@ pub async fn enter_rx(tx_sm: &mut SM<0>, rx_sm: &mut SM<1>, pin: &mut Pin<'stati
         yield_now().await;
     }
 
-    Timer::after(Duration::from_micros(2000000 * 11 / USART_SPEED)).await;
+    Timer::after(Duration::from_micros(2000000 * 11 / current_baud() as u64)).await;
 
     tx_sm.set_enable(false);
     pin.set_drive_strength(embassy_rp::gpio::Drive::_2mA);
@@ -71,8 +92,14 @@ pub async fn half_duplex_task(mut tx_sm: SM<0>, mut rx_sm: SM<1>, mut pin: Pin<'
     let mut buf = [0u8; 4];
 
     loop {
-        match select(OTHER_SIDE_TX.read(&mut buf), rx_sm.rx().wait_pull()).await {
-            select::Either::First(n) => {
+        match select3(
+            OTHER_SIDE_TX.read(&mut buf),
+            rx_sm.rx().wait_pull(),
+            BAUD_CHANGED.wait(),
+        )
+        .await
+        {
+            select::Either3::First(n) => {
                 // let now = Instant::now();
                 // crate::log::info!("sending bytes: {:?}", &buf[..n]);
                 enter_tx(&mut tx_sm, &mut rx_sm, &mut pin);
@@ -82,7 +109,7 @@ pub async fn half_duplex_task(mut tx_sm: SM<0>, mut rx_sm: SM<1>, mut pin: Pin<'
                 enter_rx(&mut tx_sm, &mut rx_sm, &mut pin).await;
                 // log::info!("sent bytes: {} in {}", &buf[..n], now.elapsed());
             }
-            select::Either::Second(x) => {
+            select::Either3::Second(x) => {
                 crate::set_status_led(embassy_rp::gpio::Level::High);
                 let x = x.to_be_bytes()[0];
                 // crate::log::info!("got byte: {:08b}: {}", 255 - x, 255 - x);
@@ -97,12 +124,22 @@ pub async fn half_duplex_task(mut tx_sm: SM<0>, mut rx_sm: SM<1>, mut pin: Pin<'
                 }
                 crate::set_status_led(embassy_rp::gpio::Level::Low);
             }
+            select::Either3::Third(rate) => {
+                let divider = pio_freq(rate);
+                tx_sm.set_clock_divider(divider);
+                tx_sm.clkdiv_restart();
+                rx_sm.set_clock_divider(divider);
+                rx_sm.clkdiv_restart();
+
+                // anything half received was at the old speed
+                rx_sm.restart();
+            }
         }
     }
 }
 
-fn pio_freq() -> fixed::FixedU32<fixed::types::extra::U8> {
-    (U56F8::from_num(clocks::clk_sys_freq()) / (8 * USART_SPEED)).to_fixed()
+fn pio_freq(baud: u32) -> fixed::FixedU32<fixed::types::extra::U8> {
+    (U56F8::from_num(clocks::clk_sys_freq()) / (8 * baud as u64)).to_fixed()
 }
 
 pub fn half_duplex_task_tx(
@@ -125,7 +162,7 @@ pub fn half_duplex_task_tx(
 
     let mut cfg = embassy_rp::pio::Config::default();
     cfg.use_program(&common.load_program(&tx_prog.program), &[]);
-    cfg.clock_divider = pio_freq();
+    cfg.clock_divider = pio_freq(current_baud());
     cfg.set_out_pins(&[pin]);
     cfg.set_set_pins(&[pin]);
     cfg.fifo_join = FifoJoin::TxOnly;
@@ -163,7 +200,7 @@ pub fn half_duplex_task_rx(
 
     let mut cfg = embassy_rp::pio::Config::default();
     cfg.use_program(&common.load_program(&rx_prog.program), &[]);
-    cfg.clock_divider = pio_freq();
+    cfg.clock_divider = pio_freq(current_baud());
     cfg.set_in_pins(&[pin]);
     cfg.set_jmp_pin(pin);
     cfg.shift_out.auto_fill = false;
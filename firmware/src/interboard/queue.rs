@@ -0,0 +1,443 @@
+//! The queue of messages waiting to go to the other side
+//!
+//! Rather than a plain channel, what happens to a message depends on its kind:
+//!
+//! - key events are never held up: if the queue is full something less
+//!   important is thrown out to make room
+//! - state syncs (and the like) replace any queued message of the same kind, as
+//!   only the latest one matters, and queued mouse movements are merged
+//! - unreliable messages are dropped if the queue is full, reliable ones wait
+//!   for space
+//! - while the link is down only link control messages are sent. A few
+//!   messages to or from the host are held until it comes back, as nothing
+//!   would resend them, and everything else is resynced when the link comes
+//!   back
+//!
+//! Anything dropped or merged away is counted per kind.
+
+use core::{cell::RefCell, future::poll_fn, task::Poll};
+
+use embassy_sync::{
+    blocking_mutex::{raw::ThreadModeRawMutex, Mutex},
+    waitqueue::{MultiWakerRegistration, WakerRegistration},
+};
+use portable_atomic::{AtomicU32, Ordering};
+use shared::device_to_host::DroppedMessages;
+
+use crate::messages::{device_to_device::DeviceToDevice, TransmittedMessage};
+
+use super::link;
+
+const QUEUE_LEN: usize = 16;
+
+/// How many messages to or from the host are held while the link is down,
+/// any more are dropped
+const MAX_HELD_FOR_LINK: usize = 4;
+
+/// Retries allowed for reliable messages that aren't key events
+const MAX_RETRIES: u8 = 8;
+
+/// Mouse reports go out as low latency messages, if one doesn't make it
+/// quickly a newer one will be along soon
+const MAX_MOUSE_RETRIES: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Keys,
+    Mouse,
+    StateSync,
+    Animation,
+    Host,
+    Control,
+}
+
+impl Kind {
+    fn of(msg: &DeviceToDevice) -> Self {
+        match msg {
+            DeviceToDevice::KeyPress(..)
+            | DeviceToDevice::KeyRelease(..)
+            | DeviceToDevice::SyncHeldKeys(_) => Kind::Keys,
+            DeviceToDevice::ForwardedToHostMouse(_) => Kind::Mouse,
+            DeviceToDevice::SyncMouseState(_)
+            | DeviceToDevice::SyncLayer(_)
+            | DeviceToDevice::SyncModifiers(_)
+            | DeviceToDevice::SyncBrightness(_)
+            | DeviceToDevice::SyncIdle(_)
+            | DeviceToDevice::SyncSuspended(_)
+            | DeviceToDevice::SyncHostOs(_)
+            | DeviceToDevice::RequestResync => Kind::StateSync,
+            DeviceToDevice::SetAnimation(..) | DeviceToDevice::SyncAnimation(..) => Kind::Animation,
+            DeviceToDevice::ForwardedFromHost(_) | DeviceToDevice::ForwardedToHost(_) => Kind::Host,
+            DeviceToDevice::Ping(_)
+            | DeviceToDevice::Pong { .. }
+            | DeviceToDevice::ProposeBaud(_)
+            | DeviceToDevice::AcceptBaud(_)
+            | DeviceToDevice::AnnounceRole(_) => Kind::Control,
+        }
+    }
+
+    fn max_retries(self) -> Option<u8> {
+        match self {
+            Kind::Keys => None,
+            Kind::Mouse => Some(MAX_MOUSE_RETRIES),
+            _ => Some(MAX_RETRIES),
+        }
+    }
+}
+
+/// Whether `new` should replace (or be merged into) the queued `old`
+fn coalesces(old: &DeviceToDevice, new: &DeviceToDevice) -> bool {
+    matches!(
+        (old, new),
+        (DeviceToDevice::Ping(_), DeviceToDevice::Ping(_))
+            | (DeviceToDevice::Pong { .. }, DeviceToDevice::Pong { .. })
+            | (
+                DeviceToDevice::AnnounceRole(_),
+                DeviceToDevice::AnnounceRole(_)
+            )
+            | (
+                DeviceToDevice::SyncMouseState(_),
+                DeviceToDevice::SyncMouseState(_)
+            )
+            | (DeviceToDevice::SyncLayer(_), DeviceToDevice::SyncLayer(_))
+            | (
+                DeviceToDevice::SyncModifiers(_),
+                DeviceToDevice::SyncModifiers(_)
+            )
+            | (
+                DeviceToDevice::SyncBrightness(_),
+                DeviceToDevice::SyncBrightness(_)
+            )
+            | (DeviceToDevice::SyncIdle(_), DeviceToDevice::SyncIdle(_))
+            | (
+                DeviceToDevice::SyncSuspended(_),
+                DeviceToDevice::SyncSuspended(_)
+            )
+            | (DeviceToDevice::SyncHostOs(_), DeviceToDevice::SyncHostOs(_))
+            | (DeviceToDevice::RequestResync, DeviceToDevice::RequestResync)
+            | (
+                DeviceToDevice::SetAnimation(..),
+                DeviceToDevice::SetAnimation(..)
+            )
+            | (
+                DeviceToDevice::SyncAnimation(..),
+                DeviceToDevice::SyncAnimation(..)
+            )
+            | (
+                DeviceToDevice::ForwardedToHostMouse(_),
+                DeviceToDevice::ForwardedToHostMouse(_)
+            )
+    )
+}
+
+fn merge(old: &mut DeviceToDevice, new: DeviceToDevice) {
+    match (old, new) {
+        (DeviceToDevice::ForwardedToHostMouse(old), DeviceToDevice::ForwardedToHostMouse(new)) => {
+            old.x = old.x.saturating_add(new.x);
+            old.y = old.y.saturating_add(new.y);
+        }
+        (old, new) => *old = new,
+    }
+}
+
+struct DropCounters {
+    keys: AtomicU32,
+    mouse: AtomicU32,
+    state_sync: AtomicU32,
+    animation: AtomicU32,
+    host: AtomicU32,
+    control: AtomicU32,
+}
+
+impl DropCounters {
+    const fn new() -> Self {
+        Self {
+            keys: AtomicU32::new(0),
+            mouse: AtomicU32::new(0),
+            state_sync: AtomicU32::new(0),
+            animation: AtomicU32::new(0),
+            host: AtomicU32::new(0),
+            control: AtomicU32::new(0),
+        }
+    }
+
+    fn bump(&self, kind: Kind) {
+        let counter = match kind {
+            Kind::Keys => &self.keys,
+            Kind::Mouse => &self.mouse,
+            Kind::StateSync => &self.state_sync,
+            Kind::Animation => &self.animation,
+            Kind::Host => &self.host,
+            Kind::Control => &self.control,
+        };
+
+        counter.add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> DroppedMessages {
+        DroppedMessages {
+            keys: self.keys.load(Ordering::Relaxed),
+            mouse: self.mouse.load(Ordering::Relaxed),
+            state_sync: self.state_sync.load(Ordering::Relaxed),
+            animation: self.animation.load(Ordering::Relaxed),
+            host: self.host.load(Ordering::Relaxed),
+            control: self.control.load(Ordering::Relaxed),
+        }
+    }
+}
+
+struct Entry {
+    msg: TransmittedMessage<DeviceToDevice>,
+    priority: u8,
+    seq: u32,
+}
+
+impl Entry {
+    fn kind(&self) -> Kind {
+        Kind::of(&self.msg.msg)
+    }
+
+    /// Greater if this entry was queued after `other`
+    fn age_cmp(&self, other: &Entry) -> core::cmp::Ordering {
+        (self.seq.wrapping_sub(other.seq) as i32).cmp(&0)
+    }
+
+    /// Lower priority numbers go first, then older messages
+    fn goes_before(&self, other: &Entry) -> bool {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| self.age_cmp(other))
+            .is_lt()
+    }
+}
+
+enum Pushed {
+    Queued,
+    Dropped,
+    Full(TransmittedMessage<DeviceToDevice>),
+}
+
+struct State {
+    entries: heapless::Vec<Entry, QUEUE_LEN>,
+    next_seq: u32,
+    receiver: WakerRegistration,
+    senders: MultiWakerRegistration<8>,
+}
+
+impl State {
+    fn push(
+        &mut self,
+        msg: TransmittedMessage<DeviceToDevice>,
+        priority: u8,
+        drops: &DropCounters,
+    ) -> Pushed {
+        let kind = Kind::of(&msg.msg);
+
+        if !link::is_up() {
+            let held = self
+                .entries
+                .iter()
+                .filter(|e| e.kind() == Kind::Host)
+                .count();
+
+            match kind {
+                Kind::Control => {}
+                Kind::Host if held < MAX_HELD_FOR_LINK => {}
+                _ => {
+                    drops.bump(kind);
+                    return Pushed::Dropped;
+                }
+            }
+        }
+
+        if let Some(queued) = self
+            .entries
+            .iter_mut()
+            .find(|e| coalesces(&e.msg.msg, &msg.msg))
+        {
+            // keep the queued message reliable if either of them was
+            queued.msg.timeout = queued.msg.timeout.or(msg.timeout);
+            merge(&mut queued.msg.msg, msg.msg);
+            queued.priority = queued.priority.min(priority);
+            drops.bump(kind);
+            return Pushed::Queued;
+        }
+
+        if self.entries.is_full() {
+            if kind == Kind::Keys {
+                let Some(victim) = self.victim() else {
+                    return Pushed::Full(msg);
+                };
+
+                let victim = self.entries.swap_remove(victim);
+                drops.bump(victim.kind());
+            } else if msg.timeout.is_none() {
+                drops.bump(kind);
+                return Pushed::Dropped;
+            } else {
+                return Pushed::Full(msg);
+            }
+        }
+
+        let entry = Entry {
+            msg,
+            priority,
+            seq: self.next_seq,
+        };
+        self.next_seq = self.next_seq.wrapping_add(1);
+
+        match self.entries.push(entry) {
+            Ok(()) => {
+                self.receiver.wake();
+                Pushed::Queued
+            }
+            Err(entry) => Pushed::Full(entry.msg),
+        }
+    }
+
+    /// The least important message that isn't a key event: unreliable before
+    /// reliable, then the lowest priority, then the newest
+    fn victim(&self) -> Option<usize> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.kind() != Kind::Keys)
+            .max_by(|(_, a), (_, b)| {
+                (a.msg.timeout.is_none(), a.priority)
+                    .cmp(&(b.msg.timeout.is_none(), b.priority))
+                    .then_with(|| a.age_cmp(b))
+            })
+            .map(|(idx, _)| idx)
+    }
+
+    fn pop(&mut self) -> Option<TransmittedMessage<DeviceToDevice>> {
+        let mut next = None::<usize>;
+        let link_up = link::is_up();
+
+        for (idx, e) in self.entries.iter().enumerate() {
+            // anything held for the link waits until it comes back
+            if !link_up && e.kind() != Kind::Control {
+                continue;
+            }
+
+            if next.map_or(true, |n| e.goes_before(&self.entries[n])) {
+                next = Some(idx);
+            }
+        }
+
+        let entry = self.entries.remove(next?);
+        self.senders.wake();
+
+        let kind = entry.kind();
+        let mut msg = entry.msg;
+        msg.max_retries = kind.max_retries();
+
+        Some(msg)
+    }
+}
+
+pub struct OutboundQueue {
+    state: Mutex<ThreadModeRawMutex, RefCell<State>>,
+    drops: DropCounters,
+}
+
+impl OutboundQueue {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                entries: heapless::Vec::new(),
+                next_seq: 0,
+                receiver: WakerRegistration::new(),
+                senders: MultiWakerRegistration::new(),
+            })),
+            drops: DropCounters::new(),
+        }
+    }
+
+    /// Queue a message, waiting for space if the policy for it says to
+    pub async fn send(&self, msg: TransmittedMessage<DeviceToDevice>, priority: u8) {
+        let mut msg = Some(msg);
+
+        poll_fn(|cx| {
+            self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+
+                match s.push(msg.take().unwrap(), priority, &self.drops) {
+                    Pushed::Queued | Pushed::Dropped => Poll::Ready(()),
+                    Pushed::Full(m) => {
+                        msg = Some(m);
+                        s.senders.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+
+    /// Queue a message without waiting, fails if it was dropped or there was
+    /// no space
+    pub fn try_send(
+        &self,
+        msg: TransmittedMessage<DeviceToDevice>,
+        priority: u8,
+    ) -> Result<(), ()> {
+        self.state
+            .lock(|s| match s.borrow_mut().push(msg, priority, &self.drops) {
+                Pushed::Queued => Ok(()),
+                Pushed::Dropped | Pushed::Full(_) => Err(()),
+            })
+    }
+
+    pub async fn receive(&self) -> TransmittedMessage<DeviceToDevice> {
+        poll_fn(|cx| {
+            self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+
+                match s.pop() {
+                    Some(msg) => Poll::Ready(msg),
+                    None => {
+                        s.receiver.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+
+    /// Throw away everything but link control messages and a few for the
+    /// host, for when the link goes down
+    pub fn purge(&self) {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            let mut held = 0;
+
+            s.entries.retain(|e| {
+                let keep = match e.kind() {
+                    Kind::Control => true,
+                    Kind::Host => {
+                        held += 1;
+                        held <= MAX_HELD_FOR_LINK
+                    }
+                    _ => false,
+                };
+                if !keep {
+                    self.drops.bump(e.kind());
+                }
+                keep
+            });
+
+            s.senders.wake();
+        })
+    }
+
+    /// Let go of the messages held while the link was down
+    pub fn release_held(&self) {
+        self.state.lock(|s| s.borrow_mut().receiver.wake())
+    }
+
+    pub fn dropped(&self) -> DroppedMessages {
+        self.drops.snapshot()
+    }
+}
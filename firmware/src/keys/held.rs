@@ -0,0 +1,45 @@
+use keyberon::layout::Event;
+use serde::{Deserialize, Serialize};
+
+use super::matrix::{COLS, ROWS};
+
+/// A set of held keys, one bit per matrix coordinate
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub struct HeldKeys(u64);
+
+impl HeldKeys {
+    pub const EMPTY: Self = Self(0);
+
+    fn bit(x: u8, y: u8) -> Option<u64> {
+        let (x, y) = (x as usize, y as usize);
+        (x < ROWS && y < COLS).then(|| 1 << (x * COLS + y))
+    }
+
+    pub fn update(&mut self, event: Event) {
+        let (x, y) = event.coord();
+        let Some(bit) = Self::bit(x, y) else {
+            return;
+        };
+
+        if event.is_press() {
+            self.0 |= bit;
+        } else {
+            self.0 &= !bit;
+        }
+    }
+
+    pub fn contains(&self, x: u8, y: u8) -> bool {
+        Self::bit(x, y).map_or(false, |bit| self.0 & bit != 0)
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = (u8, u8)> {
+        (0..ROWS as u8)
+            .flat_map(|x| (0..COLS as u8).map(move |y| (x, y)))
+            .filter(move |&(x, y)| self.contains(x, y))
+    }
+
+    pub fn take(&mut self) -> Self {
+        core::mem::take(self)
+    }
+}
@@ -0,0 +1,304 @@
+//! Which keys type which characters on the host
+//!
+//! Keys are sent by position and the host decides what they mean, so anything
+//! typing text needs to know the layout the host is set to. Set from the host
+//! and remembered across reboots, each side keeps its own copy.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use packed_struct::PrimitiveEnum;
+use shared::host_to_device::HostLayout;
+use usbd_human_interface_device::page::Keyboard;
+
+use crate::{flash, utils::log};
+
+static CURRENT: Mutex<ThreadModeRawMutex, Cell<HostLayout>> = Mutex::new(Cell::new(HostLayout::Us));
+
+pub async fn init() {
+    if let Some(layout) = flash::get::<HostLayout>().await {
+        log::info!("Loaded host layout: {:?}", layout);
+        CURRENT.lock(|c| c.set(layout));
+    }
+}
+
+pub fn current() -> HostLayout {
+    CURRENT.lock(|c| c.get())
+}
+
+pub fn set(layout: HostLayout) {
+    CURRENT.lock(|c| c.set(layout));
+
+    flash::save_later(&layout);
+}
+
+/// A key and the modifiers to hold with it
+#[derive(Clone, Copy)]
+pub struct Keystroke {
+    pub key: Keyboard,
+    pub shift: bool,
+    pub alt_gr: bool,
+    /// Dead keys wait to combine with the next key, a space after them types
+    /// the character itself
+    pub dead: bool,
+}
+
+impl Keystroke {
+    const fn new(key: Keyboard) -> Self {
+        Self {
+            key,
+            shift: false,
+            alt_gr: false,
+            dead: false,
+        }
+    }
+
+    const fn shift(self) -> Self {
+        Self {
+            shift: true,
+            ..self
+        }
+    }
+
+    const fn alt_gr(self) -> Self {
+        Self {
+            alt_gr: true,
+            ..self
+        }
+    }
+
+    const fn dead(self) -> Self {
+        Self { dead: true, ..self }
+    }
+
+    /// The modifiers followed by the key, to press together
+    pub fn keys(&self) -> heapless::Vec<Keyboard, 3> {
+        let mut keys = heapless::Vec::new();
+
+        if self.shift {
+            let _ = keys.push(Keyboard::LeftShift);
+        }
+        if self.alt_gr {
+            let _ = keys.push(Keyboard::RightAlt);
+        }
+        let _ = keys.push(self.key);
+
+        keys
+    }
+}
+
+/// The keystroke typing a character with the host's layout, if it has one
+pub fn keystroke(c: char) -> Option<Keystroke> {
+    keystroke_for(current(), c)
+}
+
+pub fn keystroke_for(layout: HostLayout, c: char) -> Option<Keystroke> {
+    match c {
+        'a'..='z' => letter(layout, c).map(Keystroke::new),
+        'A'..='Z' => letter(layout, c.to_ascii_lowercase()).map(|k| Keystroke::new(k).shift()),
+        '\n' => Some(Keystroke::new(Keyboard::ReturnEnter)),
+        '\t' => Some(Keystroke::new(Keyboard::Tab)),
+        ' ' => Some(Keystroke::new(Keyboard::Space)),
+        _ => match layout {
+            HostLayout::Us => us(c),
+            HostLayout::Uk => uk(c),
+            HostLayout::De => de(c),
+            HostLayout::Fr => fr(c),
+        },
+    }
+}
+
+/// The key for a lower case letter, which is where US has it other than a few
+fn letter(layout: HostLayout, c: char) -> Option<Keyboard> {
+    let c = match (layout, c) {
+        (HostLayout::De, 'y') => 'z',
+        (HostLayout::De, 'z') => 'y',
+        (HostLayout::Fr, 'a') => 'q',
+        (HostLayout::Fr, 'q') => 'a',
+        (HostLayout::Fr, 'z') => 'w',
+        (HostLayout::Fr, 'w') => 'z',
+        (HostLayout::Fr, 'm') => return Some(Keyboard::Semicolon),
+        _ => c,
+    };
+
+    Keyboard::from_primitive(Keyboard::A as u8 + (c as u8 - b'a'))
+}
+
+/// The key in the number row that is labelled with this digit
+fn digit(c: char) -> Option<Keyboard> {
+    match c {
+        '0' => Some(Keyboard::Keyboard0),
+        '1'..='9' => Keyboard::from_primitive(Keyboard::Keyboard1 as u8 + (c as u8 - b'1')),
+        _ => None,
+    }
+}
+
+fn us(c: char) -> Option<Keystroke> {
+    let k = Keystroke::new;
+
+    Some(match c {
+        '0'..='9' => k(digit(c)?),
+        '!' => k(Keyboard::Keyboard1).shift(),
+        '@' => k(Keyboard::Keyboard2).shift(),
+        '#' => k(Keyboard::Keyboard3).shift(),
+        '$' => k(Keyboard::Keyboard4).shift(),
+        '%' => k(Keyboard::Keyboard5).shift(),
+        '^' => k(Keyboard::Keyboard6).shift(),
+        '&' => k(Keyboard::Keyboard7).shift(),
+        '*' => k(Keyboard::Keyboard8).shift(),
+        '(' => k(Keyboard::Keyboard9).shift(),
+        ')' => k(Keyboard::Keyboard0).shift(),
+        '-' => k(Keyboard::Minus),
+        '_' => k(Keyboard::Minus).shift(),
+        '=' => k(Keyboard::Equal),
+        '+' => k(Keyboard::Equal).shift(),
+        '[' => k(Keyboard::LeftBrace),
+        '{' => k(Keyboard::LeftBrace).shift(),
+        ']' => k(Keyboard::RightBrace),
+        '}' => k(Keyboard::RightBrace).shift(),
+        '\\' => k(Keyboard::Backslash),
+        '|' => k(Keyboard::Backslash).shift(),
+        ';' => k(Keyboard::Semicolon),
+        ':' => k(Keyboard::Semicolon).shift(),
+        '\'' => k(Keyboard::Apostrophe),
+        '"' => k(Keyboard::Apostrophe).shift(),
+        '`' => k(Keyboard::Grave),
+        '~' => k(Keyboard::Grave).shift(),
+        ',' => k(Keyboard::Comma),
+        '<' => k(Keyboard::Comma).shift(),
+        '.' => k(Keyboard::Dot),
+        '>' => k(Keyboard::Dot).shift(),
+        '/' => k(Keyboard::ForwardSlash),
+        '?' => k(Keyboard::ForwardSlash).shift(),
+        _ => return None,
+    })
+}
+
+/// Mostly US with a few symbols moved around
+fn uk(c: char) -> Option<Keystroke> {
+    let k = Keystroke::new;
+
+    Some(match c {
+        '"' => k(Keyboard::Keyboard2).shift(),
+        '£' => k(Keyboard::Keyboard3).shift(),
+        '€' => k(Keyboard::Keyboard4).alt_gr(),
+        '@' => k(Keyboard::Apostrophe).shift(),
+        '#' => k(Keyboard::NonUSHash),
+        '~' => k(Keyboard::NonUSHash).shift(),
+        '\\' => k(Keyboard::NonUSBackslash),
+        '|' => k(Keyboard::NonUSBackslash).shift(),
+        '¬' => k(Keyboard::Grave).shift(),
+        '¦' => k(Keyboard::Grave).alt_gr(),
+        _ => return us(c),
+    })
+}
+
+fn de(c: char) -> Option<Keystroke> {
+    let k = Keystroke::new;
+
+    Some(match c {
+        '0'..='9' => k(digit(c)?),
+        '!' => k(Keyboard::Keyboard1).shift(),
+        '"' => k(Keyboard::Keyboard2).shift(),
+        '²' => k(Keyboard::Keyboard2).alt_gr(),
+        '§' => k(Keyboard::Keyboard3).shift(),
+        '³' => k(Keyboard::Keyboard3).alt_gr(),
+        '$' => k(Keyboard::Keyboard4).shift(),
+        '%' => k(Keyboard::Keyboard5).shift(),
+        '&' => k(Keyboard::Keyboard6).shift(),
+        '/' => k(Keyboard::Keyboard7).shift(),
+        '{' => k(Keyboard::Keyboard7).alt_gr(),
+        '(' => k(Keyboard::Keyboard8).shift(),
+        '[' => k(Keyboard::Keyboard8).alt_gr(),
+        ')' => k(Keyboard::Keyboard9).shift(),
+        ']' => k(Keyboard::Keyboard9).alt_gr(),
+        '=' => k(Keyboard::Keyboard0).shift(),
+        '}' => k(Keyboard::Keyboard0).alt_gr(),
+        'ß' => k(Keyboard::Minus),
+        '?' => k(Keyboard::Minus).shift(),
+        '\\' => k(Keyboard::Minus).alt_gr(),
+        '´' => k(Keyboard::Equal).dead(),
+        '`' => k(Keyboard::Equal).shift().dead(),
+        'ü' => k(Keyboard::LeftBrace),
+        'Ü' => k(Keyboard::LeftBrace).shift(),
+        '+' => k(Keyboard::RightBrace),
+        '*' => k(Keyboard::RightBrace).shift(),
+        '~' => k(Keyboard::RightBrace).alt_gr(),
+        'ö' => k(Keyboard::Semicolon),
+        'Ö' => k(Keyboard::Semicolon).shift(),
+        'ä' => k(Keyboard::Apostrophe),
+        'Ä' => k(Keyboard::Apostrophe).shift(),
+        '#' => k(Keyboard::NonUSHash),
+        '\'' => k(Keyboard::NonUSHash).shift(),
+        '^' => k(Keyboard::Grave).dead(),
+        '°' => k(Keyboard::Grave).shift(),
+        '<' => k(Keyboard::NonUSBackslash),
+        '>' => k(Keyboard::NonUSBackslash).shift(),
+        '|' => k(Keyboard::NonUSBackslash).alt_gr(),
+        ',' => k(Keyboard::Comma),
+        ';' => k(Keyboard::Comma).shift(),
+        '.' => k(Keyboard::Dot),
+        ':' => k(Keyboard::Dot).shift(),
+        '-' => k(Keyboard::ForwardSlash),
+        '_' => k(Keyboard::ForwardSlash).shift(),
+        '@' => k(Keyboard::Q).alt_gr(),
+        '€' => k(Keyboard::E).alt_gr(),
+        'µ' => k(Keyboard::M).alt_gr(),
+        _ => return None,
+    })
+}
+
+fn fr(c: char) -> Option<Keystroke> {
+    let k = Keystroke::new;
+
+    Some(match c {
+        // the number row types symbols unless shifted
+        '0'..='9' => k(digit(c)?).shift(),
+        '²' => k(Keyboard::Grave),
+        '&' => k(Keyboard::Keyboard1),
+        'é' => k(Keyboard::Keyboard2),
+        '~' => k(Keyboard::Keyboard2).alt_gr().dead(),
+        '"' => k(Keyboard::Keyboard3),
+        '#' => k(Keyboard::Keyboard3).alt_gr(),
+        '\'' => k(Keyboard::Keyboard4),
+        '{' => k(Keyboard::Keyboard4).alt_gr(),
+        '(' => k(Keyboard::Keyboard5),
+        '[' => k(Keyboard::Keyboard5).alt_gr(),
+        '-' => k(Keyboard::Keyboard6),
+        '|' => k(Keyboard::Keyboard6).alt_gr(),
+        'è' => k(Keyboard::Keyboard7),
+        '`' => k(Keyboard::Keyboard7).alt_gr().dead(),
+        '_' => k(Keyboard::Keyboard8),
+        '\\' => k(Keyboard::Keyboard8).alt_gr(),
+        'ç' => k(Keyboard::Keyboard9),
+        '^' => k(Keyboard::Keyboard9).alt_gr(),
+        'à' => k(Keyboard::Keyboard0),
+        '@' => k(Keyboard::Keyboard0).alt_gr(),
+        ')' => k(Keyboard::Minus),
+        '°' => k(Keyboard::Minus).shift(),
+        ']' => k(Keyboard::Minus).alt_gr(),
+        '=' => k(Keyboard::Equal),
+        '+' => k(Keyboard::Equal).shift(),
+        '}' => k(Keyboard::Equal).alt_gr(),
+        '¨' => k(Keyboard::LeftBrace).shift().dead(),
+        '$' => k(Keyboard::RightBrace),
+        '£' => k(Keyboard::RightBrace).shift(),
+        '¤' => k(Keyboard::RightBrace).alt_gr(),
+        'ù' => k(Keyboard::Apostrophe),
+        '%' => k(Keyboard::Apostrophe).shift(),
+        '*' => k(Keyboard::NonUSHash),
+        'µ' => k(Keyboard::NonUSHash).shift(),
+        '<' => k(Keyboard::NonUSBackslash),
+        '>' => k(Keyboard::NonUSBackslash).shift(),
+        ',' => k(Keyboard::M),
+        '?' => k(Keyboard::M).shift(),
+        ';' => k(Keyboard::Comma),
+        '.' => k(Keyboard::Comma).shift(),
+        ':' => k(Keyboard::Dot),
+        '/' => k(Keyboard::Dot).shift(),
+        '!' => k(Keyboard::ForwardSlash),
+        '§' => k(Keyboard::ForwardSlash).shift(),
+        '€' => k(Keyboard::E).alt_gr(),
+        _ => return None,
+    })
+}
@@ -21,7 +21,7 @@ pub fn chorder() -> super::chord::Chorder {
         [(0, 2), (0, 3)] => [(5, 4)],
     )
 }
-pub static LAYERS: ::keyberon::layout::Layers<10, 6, 3, super::CustomEvent> = [
+pub static LAYERS: ::keyberon::layout::Layers<10, 6, 4, super::CustomEvent> = [
   [
     [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Q), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::W), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::E), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::R), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::T), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Y), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::U), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::I), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::O), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::P), ],
     [::keyberon::action::Action::HoldTap(
@@ -118,7 +118,7 @@ pub static LAYERS: ::keyberon::layout::Layers<10, 6, 3, super::CustomEvent> = [
         config: ::keyberon::action::HoldTapConfig::HoldOnOtherKeyPress,
         tap_hold_interval: 200,
     }), ],
-    [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LAlt), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Space), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Equal), ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::LAlt), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Space), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Equal), ::keyberon::action::Action::Layer(2), ],
     [::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb1].as_slice()), ::keyberon::action::Action::Custom(super::CustomEvent::MouseLeft), ::keyberon::action::Action::Custom(super::CustomEvent::MouseRight), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb2].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb3].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb4].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb5].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb6].as_slice()), ::keyberon::action::Action::MultipleKeyCodes(&[::keyberon::key_code::KeyCode::LCtrl, ::keyberon::key_code::KeyCode::Kb7].as_slice()), ::keyberon::action::Action::NoOp, ],
   ],
@@ -154,8 +154,16 @@ pub static LAYERS: ::keyberon::layout::Layers<10, 6, 3, super::CustomEvent> = [
         config: ::keyberon::action::HoldTapConfig::HoldOnOtherKeyPress,
         tap_hold_interval: 200,
     }), ],
-    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Equal), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::End), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::Custom(super::CustomEvent::BrightnessUp), ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::Equal), ::keyberon::action::Action::Custom(super::CustomEvent::BrightnessDown), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::KeyCode(::keyberon::key_code::KeyCode::End), ::keyberon::action::Action::Custom(super::CustomEvent::BrightnessToggle), ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::Custom(super::CustomEvent::MouseLeft), ::keyberon::action::Action::Custom(super::CustomEvent::MouseRight), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
     [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
   ],
+  [
+    [::keyberon::action::Action::Custom(super::CustomEvent::TypeSnippet(0)), ::keyberon::action::Action::Custom(super::CustomEvent::TypeSnippet(1)), ::keyberon::action::Action::Custom(super::CustomEvent::TypeSnippet(2)), ::keyberon::action::Action::Custom(super::CustomEvent::TypeSnippet(3)), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::Custom(super::CustomEvent::SetHostOs(Some(::shared::host_to_device::HostOs::Linux))), ::keyberon::action::Action::Custom(super::CustomEvent::SetHostOs(Some(::shared::host_to_device::HostOs::Mac))), ::keyberon::action::Action::Custom(super::CustomEvent::SetHostOs(Some(::shared::host_to_device::HostOs::Windows))), ::keyberon::action::Action::Custom(super::CustomEvent::SetHostOs(None)), ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+    [::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ::keyberon::action::Action::NoOp, ],
+  ],
 ];
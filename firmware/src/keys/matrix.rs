@@ -0,0 +1,6 @@
+//! Size of the (chord-processed) key matrix, across both sides
+//!
+//! Rows 0 to 3 are switches, rows 4 and 5 are chords, as in the layout.
+
+pub const ROWS: usize = 6;
+pub const COLS: usize = 10;
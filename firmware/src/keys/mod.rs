@@ -1,31 +1,49 @@
+use core::cell::Cell;
+
 use embassy_executor::Spawner;
-use embassy_futures::select::select;
+use embassy_futures::{
+    join::join,
+    select::{select, select3, Either3},
+};
 use embassy_rp::gpio::{Input, Output};
 use embassy_sync::{
-    blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, pubsub::PubSubChannel,
+    blocking_mutex::{raw::ThreadModeRawMutex, Mutex},
+    channel::Channel,
+    pubsub::{PubSubChannel, Publisher},
 };
 use embassy_time::Duration;
 use keyberon::{key_code::KeyCode, layout::Event};
 use packed_struct::PrimitiveEnum;
+use portable_atomic::{AtomicU8, Ordering};
+use shared::host_to_device::HostOs;
 use usbd_human_interface_device::device::keyboard::NKROBootKeyboardReport;
 
 use crate::{
-    interboard::{self, THIS_SIDE_MESSAGE_BUS},
+    host_os,
+    interboard::{self, link::LinkState, THIS_SIDE_MESSAGE_BUS},
     messages::{
-        device_to_device::{DeviceToDevice, MouseState},
+        device_to_device::{DeviceToDevice, Modifiers, MouseState},
         reliable_msg,
     },
-    side,
+    metrics,
+    rgb::brightness::{self, Adjust},
+    side::{self, Role},
     usb::hid::publish_keyboard_report,
     utils::Ticker,
 };
 
-use self::{chord::ChordingEngine, layout::LAYERS};
+use self::{chord::ChordingEngine, held::HeldKeys, layout::LAYERS};
 
 #[derive(Clone, Copy)]
 pub enum UnicodeMode {
     Linux,
     Mac,
+    /// Needs [WinCompose](https://github.com/samhocevar/wincompose) running,
+    /// with right alt as the compose key
+    WinCompose,
+    /// Alt and numpad plus, needs `EnableHexNumpad` set in the registry. Only
+    /// reliable for characters that fit in one utf-16 unit.
+    HexNumpad,
 }
 
 #[derive(Clone, Copy)]
@@ -34,23 +52,99 @@ pub enum CustomEvent {
     MouseRight,
     MouseScroll,
     TypeUnicode(&'static str),
+    /// Type the snippet at this index, see [`snippets`]
+    TypeSnippet(u8),
+    BrightnessUp,
+    BrightnessDown,
+    BrightnessToggle,
+    /// Force the host OS, or go back to guessing it with `None`
+    SetHostOs(Option<HostOs>),
 }
 
 pub mod chord;
+pub mod held;
+pub mod host_layout;
 pub mod layout;
+pub mod matrix;
 pub mod scan;
-mod unicode;
+pub mod snippets;
+pub mod unicode;
 
 /// Raw matrix presses and releases
 pub static MATRIX_EVENTS: PubSubChannel<ThreadModeRawMutex, keyberon::layout::Event, 4, 4, 1> =
     PubSubChannel::new();
 
-/// Chord-processed events
-pub static KEY_EVENTS: PubSubChannel<ThreadModeRawMutex, keyberon::layout::Event, 4, 4, 2> =
+/// Chord-processed events, from both sides
+pub static KEY_EVENTS: PubSubChannel<ThreadModeRawMutex, keyberon::layout::Event, 4, 6, 2> =
     PubSubChannel::new();
 
+/// Every key held on either side, going by what's been published to
+/// [`KEY_EVENTS`]
+static HELD_KEYS: Mutex<ThreadModeRawMutex, Cell<HeldKeys>> =
+    Mutex::new(Cell::new(HeldKeys::EMPTY));
+
+async fn publish_key_event(
+    publisher: &Publisher<'_, ThreadModeRawMutex, Event, 4, 6, 2>,
+    evt: Event,
+) {
+    HELD_KEYS.lock(|h| {
+        let mut held = h.get();
+        held.update(evt);
+        h.set(held);
+    });
+
+    publisher.publish(evt).await;
+}
+
 static KEYS_TO_OTHER_SIDE: Channel<ThreadModeRawMutex, keyberon::layout::Event, 4> = Channel::new();
 
+static CURRENT_LAYER: AtomicU8 = AtomicU8::new(0);
+static CURRENT_MOUSE_STATE: AtomicU8 = AtomicU8::new(0);
+static CURRENT_MODIFIERS: AtomicU8 = AtomicU8::new(0);
+
+/// The active layer, as last seen by the primary side
+pub fn current_layer() -> u8 {
+    CURRENT_LAYER.load(Ordering::Relaxed)
+}
+
+pub fn set_current_layer(layer: u8) {
+    CURRENT_LAYER.store(layer, Ordering::Relaxed);
+}
+
+/// The mouse button state, as last seen by the primary side
+pub fn current_mouse_state() -> MouseState {
+    MouseState::from(CURRENT_MOUSE_STATE.load(Ordering::Relaxed))
+}
+
+pub fn set_current_mouse_state(state: MouseState) {
+    CURRENT_MOUSE_STATE.store(state.into(), Ordering::Relaxed);
+}
+
+/// The held modifiers and caps lock, as last seen by the primary side
+pub fn current_modifiers() -> Modifiers {
+    Modifiers::from(CURRENT_MODIFIERS.load(Ordering::Relaxed))
+}
+
+pub fn set_current_modifiers(modifiers: Modifiers) {
+    CURRENT_MODIFIERS.store(modifiers.into(), Ordering::Relaxed);
+}
+
+fn modifiers_of(keycodes: &[KeyCode]) -> Modifiers {
+    let mut m = Modifiers::new();
+
+    for k in keycodes {
+        match k {
+            KeyCode::LShift | KeyCode::RShift => m.set_shift(true),
+            KeyCode::LCtrl | KeyCode::RCtrl => m.set_ctrl(true),
+            KeyCode::LAlt | KeyCode::RAlt => m.set_alt(true),
+            KeyCode::LGui | KeyCode::RGui => m.set_gui(true),
+            _ => {}
+        }
+    }
+
+    m
+}
+
 pub type ScannerInstance<'a> = scan::Scanner<
     (Input<'a>, Input<'a>, Input<'a>, Input<'a>),
     (Output<'a>, Output<'a>, Output<'a>, Output<'a>, Output<'a>),
@@ -91,7 +185,10 @@ async fn matrix_processor() {
                 //key_events.publish(evt).await;
                 let evts = chorder.process(evt);
                 for evt in evts {
-                    key_events.publish(evt).await;
+                    if let Event::Press(x, y) = evt {
+                        metrics::key_pressed(x, y).await;
+                    }
+                    publish_key_event(&key_events, evt).await;
                     KEYS_TO_OTHER_SIDE.send(evt).await;
                 }
             }
@@ -99,7 +196,8 @@ async fn matrix_processor() {
                 let keys = chorder.tick();
                 for (x, y) in keys {
                     let evt = keyberon::layout::Event::Press(x, y);
-                    key_events.publish(evt).await;
+                    metrics::key_pressed(x, y).await;
+                    publish_key_event(&key_events, evt).await;
                     KEYS_TO_OTHER_SIDE.send(evt).await;
                 }
             }
@@ -109,11 +207,35 @@ async fn matrix_processor() {
 
 #[embassy_executor::task]
 async fn send_events_to_other_side() {
+    let mut link_sub = interboard::link::LINK_STATE_CHANGES.subscriber().unwrap();
+    let mut role_sub = side::ROLE_CHANGES.subscriber().unwrap();
+    let mut held = HeldKeys::default();
+
     loop {
-        let evt = KEYS_TO_OTHER_SIDE.receive().await;
-        let evt = match evt {
-            Event::Press(x, y) => DeviceToDevice::KeyPress(x, y),
-            Event::Release(x, y) => DeviceToDevice::KeyRelease(x, y),
+        let evt = match select3(
+            KEYS_TO_OTHER_SIDE.receive(),
+            link_sub.next_message_pure(),
+            role_sub.next_message_pure(),
+        )
+        .await
+        {
+            Either3::First(evt) => {
+                held.update(evt);
+
+                match evt {
+                    Event::Press(x, y) => DeviceToDevice::KeyPress(x, y),
+                    Event::Release(x, y) => DeviceToDevice::KeyRelease(x, y),
+                }
+            }
+            Either3::Second(LinkState::Up) => {
+                // the other side may have missed presses or releases while the
+                // link was down, tell it exactly what we're holding
+                DeviceToDevice::SyncHeldKeys(held)
+            }
+            Either3::Second(LinkState::Down) => continue,
+            // the new primary picks up from what it knows is held, make sure
+            // that's right
+            Either3::Third(_) => DeviceToDevice::SyncHeldKeys(held),
         };
         interboard::send_msg(reliable_msg(evt), 1).await;
     }
@@ -124,26 +246,79 @@ async fn receive_events_from_other_side() {
     let mut sub = crate::interboard::THIS_SIDE_MESSAGE_BUS
         .subscriber()
         .unwrap();
+    let mut link_sub = interboard::link::LINK_STATE_CHANGES.subscriber().unwrap();
     let key_events = KEY_EVENTS.publisher().unwrap();
+    let mut held = HeldKeys::default();
 
     loop {
-        let evt = match sub.next_message_pure().await {
-            DeviceToDevice::KeyPress(x, y) => Event::Press(x, y),
-            DeviceToDevice::KeyRelease(x, y) => Event::Release(x, y),
+        let evt = match select(sub.next_message_pure(), link_sub.next_message_pure()).await {
+            embassy_futures::select::Either::First(DeviceToDevice::KeyPress(x, y)) => {
+                metrics::key_pressed(x, y).await;
+                Event::Press(x, y)
+            }
+            embassy_futures::select::Either::First(DeviceToDevice::KeyRelease(x, y)) => {
+                Event::Release(x, y)
+            }
+            embassy_futures::select::Either::First(DeviceToDevice::SyncHeldKeys(keys)) => {
+                for (x, y) in held.iter().filter(|&(x, y)| !keys.contains(x, y)) {
+                    publish_key_event(&key_events, Event::Release(x, y)).await;
+                }
+                for (x, y) in keys.iter().filter(|&(x, y)| !held.contains(x, y)) {
+                    publish_key_event(&key_events, Event::Press(x, y)).await;
+                }
+                held = keys;
+                continue;
+            }
+            embassy_futures::select::Either::Second(LinkState::Down) => {
+                // the other side went away, don't leave any of its keys held
+                for (x, y) in held.take().iter() {
+                    publish_key_event(&key_events, Event::Release(x, y)).await;
+                }
+                continue;
+            }
             _ => {
                 continue;
             }
         };
 
-        key_events.publish(evt).await;
+        held.update(evt);
+        publish_key_event(&key_events, evt).await;
     }
 }
 
 #[embassy_executor::task]
 async fn key_event_processor() {
+    join(
+        side::while_primary(process_key_events),
+        release_on_demotion(),
+    )
+    .await;
+}
+
+/// Don't leave the host holding keys pressed through this side once the other
+/// side has taken over
+async fn release_on_demotion() {
+    let mut sub = side::ROLE_CHANGES.subscriber().unwrap();
+
+    loop {
+        if sub.next_message_pure().await == Role::Secondary {
+            publish_keyboard_report(NKROBootKeyboardReport::new([])).await;
+        }
+    }
+}
+
+async fn process_key_events() {
     let msg_bus_pub = THIS_SIDE_MESSAGE_BUS.publisher().unwrap();
     let mut sub = KEY_EVENTS.subscriber().unwrap();
     let mut layout = keyberon::layout::Layout::new(&LAYERS);
+
+    // keys may already be held if this side has just taken over as primary,
+    // events from before then can still be queued so skip any repeats
+    let mut pressed = HELD_KEYS.lock(|h| h.get());
+    for (x, y) in pressed.iter() {
+        layout.event(Event::Press(x, y));
+    }
+
     let mut state = heapless::Vec::<KeyCode, 24>::new();
     let mut ticker = Ticker::every(Duration::from_hz(1000));
     let mut mouse_state = MouseState::new();
@@ -153,7 +328,11 @@ async fn key_event_processor() {
             embassy_futures::select::Either::Second(evt) => {
                 // crate::utils::log::info!("evt: {:?}", evt);
 
-                layout.event(evt);
+                let (x, y) = evt.coord();
+                if evt.is_press() != pressed.contains(x, y) {
+                    pressed.update(evt);
+                    layout.event(evt);
+                }
             }
             embassy_futures::select::Either::First(_) => {
                 let cevent = layout.tick();
@@ -171,8 +350,34 @@ async fn key_event_processor() {
                                 unicode::send_unicode(msg).await;
                             }
                         }
+                        CustomEvent::TypeSnippet(index) => {
+                            if !is_press {
+                                unicode::type_snippet(index).await;
+                            }
+                        }
+                        CustomEvent::BrightnessUp => {
+                            if is_press {
+                                brightness::adjust(Adjust::Up).await;
+                            }
+                        }
+                        CustomEvent::BrightnessDown => {
+                            if is_press {
+                                brightness::adjust(Adjust::Down).await;
+                            }
+                        }
+                        CustomEvent::BrightnessToggle => {
+                            if is_press {
+                                brightness::adjust(Adjust::Toggle).await;
+                            }
+                        }
+                        CustomEvent::SetHostOs(os) => {
+                            if is_press {
+                                host_os::set(os).await;
+                            }
+                        }
                     }
 
+                    set_current_mouse_state(mouse_state);
                     let evt = DeviceToDevice::SyncMouseState(mouse_state);
 
                     interboard::send_msg(reliable_msg(evt.clone()), 1).await;
@@ -181,8 +386,24 @@ async fn key_event_processor() {
             }
         }
 
+        let layer = layout.current_layer() as u8;
+        if layer != current_layer() {
+            set_current_layer(layer);
+
+            // the other side shows layer indicators too
+            interboard::send_msg(reliable_msg(DeviceToDevice::SyncLayer(layer)), 2).await;
+        }
+
         let new_state = heapless::Vec::<_, 24>::from_iter(layout.keycodes());
 
+        let modifiers = modifiers_of(&new_state).with_caps_lock(crate::usb::hid::caps_lock());
+        if modifiers != current_modifiers() {
+            set_current_modifiers(modifiers);
+
+            let msg = DeviceToDevice::SyncModifiers(modifiers);
+            interboard::send_msg(reliable_msg(msg), 2).await;
+        }
+
         if new_state != state {
             state = new_state;
 
@@ -199,8 +420,6 @@ pub fn init(spawner: &Spawner, scanner: ScannerInstance<'static>) {
     spawner.must_spawn(matrix_scanner(scanner));
     spawner.must_spawn(send_events_to_other_side());
     spawner.must_spawn(receive_events_from_other_side());
-    if side::this_side_has_usb() {
-        spawner.must_spawn(key_event_processor());
-        spawner.must_spawn(unicode::unicode_task());
-    }
+    spawner.must_spawn(key_event_processor());
+    spawner.must_spawn(unicode::unicode_task());
 }
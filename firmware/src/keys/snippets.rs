@@ -0,0 +1,79 @@
+//! Text typed by the snippet key actions, set from the host
+//!
+//! Both sides keep a copy so that whichever is primary can type them.
+
+use core::cell::RefCell;
+
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use embassy_time::Duration;
+use serde::{Deserialize, Serialize};
+use shared::host_to_device::{SnippetEdit, MAX_SNIPPETS, MAX_SNIPPET_LEN};
+
+use crate::{flash, utils::log};
+
+pub type Snippet = heapless::String<MAX_SNIPPET_LEN>;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Snippets {
+    texts: [Snippet; MAX_SNIPPETS],
+    /// Time to wait after each keystroke (ms)
+    keystroke_delay: u8,
+}
+
+impl Snippets {
+    const EMPTY: Self = {
+        const EMPTY: Snippet = heapless::String::new();
+
+        Self {
+            texts: [EMPTY; MAX_SNIPPETS],
+            keystroke_delay: 0,
+        }
+    };
+}
+
+static SNIPPETS: Mutex<ThreadModeRawMutex, RefCell<Snippets>> =
+    Mutex::new(RefCell::new(Snippets::EMPTY));
+
+pub async fn init() {
+    if let Some(s) = flash::get::<Snippets>().await {
+        log::info!("Loaded snippets");
+        SNIPPETS.lock(|c| *c.borrow_mut() = s);
+    }
+}
+
+pub fn get(index: u8) -> Option<Snippet> {
+    SNIPPETS.lock(|s| s.borrow().texts.get(index as usize).cloned())
+}
+
+pub fn keystroke_delay() -> Duration {
+    let ms = SNIPPETS.lock(|s| s.borrow().keystroke_delay);
+
+    Duration::from_millis(ms as u64)
+}
+
+pub fn edit(edit: SnippetEdit) {
+    SNIPPETS.lock(|s| {
+        let mut s = s.borrow_mut();
+
+        match edit {
+            SnippetEdit::Clear(index) => match s.texts.get_mut(index as usize) {
+                Some(text) => text.clear(),
+                None => log::warn!("No snippet at index {}", index),
+            },
+            SnippetEdit::Append(index, more) => match s.texts.get_mut(index as usize) {
+                Some(text) => {
+                    for c in more.chars() {
+                        if text.push(c).is_err() {
+                            log::warn!("Snippet {} is full", index);
+                            break;
+                        }
+                    }
+                }
+                None => log::warn!("No snippet at index {}", index),
+            },
+            SnippetEdit::SetKeystrokeDelay(ms) => s.keystroke_delay = ms,
+        }
+
+        flash::save_later(&*s);
+    });
+}
@@ -1,127 +1,224 @@
-use embassy_os_guess::OS;
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
+use core::cell::Cell;
+
+use embassy_sync::{
+    blocking_mutex::{raw::ThreadModeRawMutex, Mutex},
+    channel::Channel,
+};
+use embassy_time::Timer;
+use packed_struct::PrimitiveEnum;
+use serde::{Deserialize, Serialize};
+use shared::host_to_device::{HostLayout, HostOs, WindowsUnicode};
 use usbd_human_interface_device::{device::keyboard::NKROBootKeyboardReport, page::Keyboard};
 
-use crate::usb::{guessed_host_os, hid::publish_keyboard_report};
+use crate::{flash, host_os, usb::hid::publish_keyboard_report, utils::log};
 
-use super::UnicodeMode;
+use super::{
+    host_layout::{self, Keystroke},
+    snippets, UnicodeMode,
+};
 
-static UNICODE_MESSAGES: Channel<ThreadModeRawMutex, &'static str, 4> = Channel::new();
+/// How to type unicode when the host runs windows, as chosen by the host
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+struct WindowsMode(WindowsUnicode);
+
+static WINDOWS_MODE: Mutex<ThreadModeRawMutex, Cell<WindowsUnicode>> =
+    Mutex::new(Cell::new(WindowsUnicode::WinCompose));
+
+pub async fn init() {
+    if let Some(WindowsMode(mode)) = flash::get::<WindowsMode>().await {
+        log::info!("Loaded windows unicode mode: {:?}", mode);
+        WINDOWS_MODE.lock(|m| m.set(mode));
+    }
+}
+
+pub fn set_windows_mode(mode: WindowsUnicode) {
+    WINDOWS_MODE.lock(|m| m.set(mode));
+
+    flash::save_later(&WindowsMode(mode));
+}
+
+enum Typing {
+    Unicode(&'static str),
+    /// The index of a snippet
+    Snippet(u8),
+}
+
+static TO_TYPE: Channel<ThreadModeRawMutex, Typing, 4> = Channel::new();
 
 pub async fn send_unicode(msg: &'static str) {
-    UNICODE_MESSAGES.send(msg).await;
+    TO_TYPE.send(Typing::Unicode(msg)).await;
+}
+
+pub async fn type_snippet(index: u8) {
+    TO_TYPE.send(Typing::Snippet(index)).await;
 }
 
 #[embassy_executor::task]
 pub async fn unicode_task() {
+    crate::side::while_primary(process_unicode).await;
+}
+
+async fn process_unicode() {
     loop {
-        let msg = UNICODE_MESSAGES.receive().await;
+        match TO_TYPE.receive().await {
+            Typing::Unicode(msg) => emit_unicode(msg).await,
+            Typing::Snippet(index) => match snippets::get(index) {
+                Some(text) => emit_text(&text).await,
+                None => log::warn!("No snippet at index {}", index),
+            },
+        }
+    }
+}
+
+fn unicode_mode() -> UnicodeMode {
+    match host_os::effective() {
+        Some(HostOs::Linux) => UnicodeMode::Linux,
+        Some(HostOs::Windows) => match WINDOWS_MODE.lock(|m| m.get()) {
+            WindowsUnicode::WinCompose => UnicodeMode::WinCompose,
+            WindowsUnicode::HexNumpad => UnicodeMode::HexNumpad,
+        },
+        _ => UnicodeMode::Mac,
+    }
+}
 
-        let mode = match guessed_host_os() {
-            Some(OS::Linux) => UnicodeMode::Linux,
-            _ => UnicodeMode::Mac,
-        };
+async fn emit_unicode(msg: &str) {
+    match unicode_mode() {
+        UnicodeMode::Linux => emit_linux(msg).await,
+        UnicodeMode::Mac => emit_mac(msg).await,
+        UnicodeMode::WinCompose => emit_win_compose(msg).await,
+        UnicodeMode::HexNumpad => emit_hex_numpad(msg).await,
+    }
+}
 
-        match mode {
-            UnicodeMode::Linux => emit_linux(msg).await,
-            UnicodeMode::Mac => emit_mac(msg).await,
+/// Type characters with their own keys where they have one, and through
+/// unicode entry otherwise
+async fn emit_text(text: &str) {
+    for c in text.chars() {
+        match host_layout::keystroke(c) {
+            Some(k) => {
+                tap_keys(&k.keys()).await;
+
+                if k.dead {
+                    tap_keys(&[Keyboard::Space]).await;
+                }
+            }
+            None => emit_unicode(c.encode_utf8(&mut [0; 4])).await,
         }
     }
 }
 
 async fn press_keys(keys: &[Keyboard]) {
     publish_keyboard_report(NKROBootKeyboardReport::new(keys.iter().copied())).await;
+
+    let delay = snippets::keystroke_delay();
+    if delay.as_ticks() > 0 {
+        Timer::after(delay).await;
+    }
 }
 
-#[allow(unused)]
 async fn tap_keys(keys: &[Keyboard]) {
     press_keys(keys).await;
-    publish_keyboard_report(NKROBootKeyboardReport::new([])).await;
-}
-
-const HEX_KEYS: [Keyboard; 16] = [
-    Keyboard::Keyboard0,
-    Keyboard::Keyboard1,
-    Keyboard::Keyboard2,
-    Keyboard::Keyboard3,
-    Keyboard::Keyboard4,
-    Keyboard::Keyboard5,
-    Keyboard::Keyboard6,
-    Keyboard::Keyboard7,
-    Keyboard::Keyboard8,
-    Keyboard::Keyboard9,
-    Keyboard::A,
-    Keyboard::B,
-    Keyboard::C,
-    Keyboard::D,
-    Keyboard::E,
-    Keyboard::F,
-];
-
-fn to_escape(c: char) -> heapless::Vec<Keyboard, 6> {
-    let c = c as u32;
-    let mut seen_nonzero = false;
+    press_keys(&[]).await;
+}
+
+/// The hex digits of a code point, without leading zeros
+fn to_escape(c: u32) -> heapless::Vec<char, 6> {
     let mut out = heapless::Vec::new();
 
-    let mut f = |n: u32| {
-        let nibble = ((c >> n) & 15u32) as usize;
-        if seen_nonzero || nibble != 0 {
-            out.push(HEX_KEYS[nibble]).unwrap();
-            seen_nonzero = true;
+    for n in (0..6).rev() {
+        let nibble = (c >> (n * 4)) & 15;
+        if !out.is_empty() || nibble != 0 {
+            out.extend(char::from_digit(nibble, 16));
         }
-    };
-
-    f(20);
-    f(16);
-    f(12);
-    f(8);
-    f(4);
-    f(0);
+    }
 
     out
 }
 
+/// The keystrokes typing the hex digits of a code point on a layout
+fn hex_keys(c: u32, layout: HostLayout) -> impl Iterator<Item = Keystroke> {
+    to_escape(c)
+        .into_iter()
+        .filter_map(move |d| host_layout::keystroke_for(layout, d))
+}
+
 async fn emit_linux(msg: &str) {
+    let layout = host_layout::current();
+
     for c in msg.chars() {
         press_keys(&[Keyboard::LeftControl, Keyboard::LeftShift, Keyboard::U]).await;
 
-        for k in to_escape(c) {
-            press_keys(&[Keyboard::LeftControl, Keyboard::LeftShift, Keyboard::U, k]).await;
+        // shift is already held, which the layouts needing it for digits want
+        for k in hex_keys(c as u32, layout) {
+            press_keys(&[
+                Keyboard::LeftControl,
+                Keyboard::LeftShift,
+                Keyboard::U,
+                k.key,
+            ])
+            .await;
         }
 
         press_keys(&[]).await;
     }
 }
 
-fn to_escape_surrogate(c: u16) -> heapless::Vec<Keyboard, 6> {
-    let mut seen_nonzero = false;
-    let mut out = heapless::Vec::new();
-
-    let mut f = |n: u16| {
-        let nibble = ((c >> n) & 15u16) as usize;
-        if seen_nonzero || nibble != 0 {
-            out.push(HEX_KEYS[nibble]).unwrap();
-            seen_nonzero = true;
-        }
-    };
-
-    f(12);
-    f(8);
-    f(4);
-    f(0);
-
-    out
-}
-
 async fn emit_mac(msg: &str) {
     press_keys(&[Keyboard::RightAlt]).await;
     embassy_time::Timer::after_millis(50).await;
     for c in msg.encode_utf16() {
         press_keys(&[Keyboard::RightAlt, Keyboard::LeftAlt]).await;
-        for k in to_escape_surrogate(c) {
-            press_keys(&[Keyboard::RightAlt, Keyboard::LeftAlt, k]).await;
+        // unicode hex input is a layout of its own, with the keys where US has them
+        for k in hex_keys(c as u32, HostLayout::Us) {
+            press_keys(&[Keyboard::RightAlt, Keyboard::LeftAlt, k.key]).await;
             press_keys(&[Keyboard::RightAlt, Keyboard::LeftAlt]).await;
         }
     }
     press_keys(&[]).await;
 }
+
+async fn emit_win_compose(msg: &str) {
+    let layout = host_layout::current();
+
+    for c in msg.chars() {
+        tap_keys(&[Keyboard::RightAlt]).await;
+        tap_keys(&[Keyboard::U]).await;
+
+        for k in hex_keys(c as u32, layout) {
+            tap_keys(&k.keys()).await;
+        }
+
+        tap_keys(&[Keyboard::ReturnEnter]).await;
+    }
+}
+
+/// Windows' hex entry only takes digits from the numpad, the letters are
+/// typed as usual
+fn numpad_hex_key(digit: char, layout: HostLayout) -> Option<Keyboard> {
+    match digit {
+        '0' => Some(Keyboard::Keypad0),
+        '1'..='9' => Keyboard::from_primitive(Keyboard::Keypad1 as u8 + (digit as u8 - b'1')),
+        _ => host_layout::keystroke_for(layout, digit).map(|k| k.key),
+    }
+}
+
+async fn emit_hex_numpad(msg: &str) {
+    let layout = host_layout::current();
+
+    for c in msg.encode_utf16() {
+        press_keys(&[Keyboard::LeftAlt]).await;
+        press_keys(&[Keyboard::LeftAlt, Keyboard::KeypadAdd]).await;
+        press_keys(&[Keyboard::LeftAlt]).await;
+
+        for d in to_escape(c as u32) {
+            if let Some(k) = numpad_hex_key(d, layout) {
+                press_keys(&[Keyboard::LeftAlt, k]).await;
+                press_keys(&[Keyboard::LeftAlt]).await;
+            }
+        }
+
+        // the character is typed when alt is let go
+        press_keys(&[]).await;
+    }
+}
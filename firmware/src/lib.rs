@@ -43,15 +43,19 @@ use crate::keys::ScannerInstance;
 mod allocator;
 #[cfg(feature = "binaryinfo")]
 pub mod binary_info;
+mod dfu;
 #[cfg(feature = "display-slint")]
 mod display;
 pub mod event;
 mod flash;
+mod host_os;
+mod idle;
 pub mod interboard;
 pub mod keys;
 pub mod logger;
 pub mod messages;
 mod metrics;
+mod power;
 pub mod rgb;
 pub mod rng;
 pub mod side;
@@ -65,7 +69,7 @@ pub fn set_status_led(value: Level) {
 
 pub static VERSION: &str = "0.1.0";
 
-fn detect_usb(pin: Input) -> bool {
+fn detect_usb(pin: &Input) -> bool {
     let connected = pin.is_high();
     log::info!("Usb connected? {}", connected);
     connected
@@ -119,19 +123,13 @@ pub async fn main(spawner: Spawner) {
     set_status_led(Level::High);
 
     let s = detect_side(Input::new(p.PIN_29, embassy_rp::gpio::Pull::Down));
-    side::init(
-        s,
-        detect_usb(Input::new(p.PIN_19, embassy_rp::gpio::Pull::Down)),
-    );
-
-    if side::this_side_has_usb() {
-        log::info!("usb connected");
-        let usb_driver = Driver::new(p.USB, UsbIrqs);
+    let vbus = Input::new(p.PIN_19, embassy_rp::gpio::Pull::Down);
+    side::init(s, detect_usb(&vbus));
 
-        usb::init(&spawner, usb_driver);
-    } else {
-        log::info!("No usb connected");
-    }
+    // usb is always set up as it may be plugged in later, the device itself
+    // only starts once vbus shows up
+    let usb_driver = Driver::new(p.USB, UsbIrqs);
+    usb::init(&spawner, usb_driver, vbus);
 
     messages::init(&spawner);
 
@@ -143,10 +141,11 @@ pub async fn main(spawner: Spawner) {
     let mut pio0 = Pio::new(p.PIO0, PioIrq0);
     interboard::init(&spawner, &mut pio0.common, pio0.sm0, pio0.sm1, p.PIN_1);
 
-    flash::init(p.FLASH, p.DMA_CH3.degrade()).await;
+    flash::init(&spawner, p.FLASH, p.DMA_CH3.degrade()).await;
+    dfu::init(&spawner);
 
     let mut pio1 = Pio::new(p.PIO1, PioIrq1);
-    rgb::init(&spawner, &mut pio1.common, pio1.sm0, p.PIN_10, p.DMA_CH2);
+    rgb::init(&spawner, &mut pio1.common, pio1.sm0, p.PIN_10, p.DMA_CH2).await;
 
     let scanner = ScannerInstance::new(
         (
@@ -165,6 +164,12 @@ pub async fn main(spawner: Spawner) {
     );
 
     keys::init(&spawner, scanner);
+    keys::snippets::init().await;
+    keys::host_layout::init().await;
+    keys::unicode::init().await;
+    idle::init(&spawner).await;
+    host_os::init().await;
+    power::init(&spawner);
 
     if side::get_side().is_right() {
         log::info!("Initializing trackpad");
@@ -1,8 +1,16 @@
 use core::hash::Hash;
 use serde::{Deserialize, Serialize};
-use shared::{device_to_host::DeviceToHost, hid::MouseReport, host_to_device::HostToDeviceMsg};
+use shared::{
+    device_to_host::DeviceToHost,
+    hid::MouseReport,
+    host_to_device::{HostOs, HostToDeviceMsg},
+};
 
-use crate::rgb::animations::AnimationSync;
+use crate::{
+    interboard::{arbitration::RoleAnnouncement, clock::SharedInstant},
+    keys::held::HeldKeys,
+    rgb::{animations::AnimationSync, brightness::Brightness},
+};
 
 #[cfg_attr(feature = "probe", derive(defmt::Format))]
 #[bitfield_struct::bitfield(u8)]
@@ -15,17 +23,52 @@ pub struct MouseState {
     _padding: u8,
 }
 
+/// Modifiers held down on the primary side, and the host's caps lock
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+#[bitfield_struct::bitfield(u8)]
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub gui: bool,
+    pub caps_lock: bool,
+    #[bits(3)]
+    _padding: u8,
+}
+
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
 #[cfg_attr(feature = "probe", derive(defmt::Format))]
 pub enum DeviceToDevice {
-    Ping,
-    Pong,
+    /// Carries when it was sent, on the sender's clock
+    Ping(u64),
+    /// When the ping being answered was sent and when it arrived, each on the
+    /// clock of the side that did it
+    Pong {
+        ping_sent: u64,
+        received: u64,
+    },
     ForwardedFromHost(HostToDeviceMsg),
     ForwardedToHost(DeviceToHost),
     ForwardedToHostMouse(MouseReport),
     KeyPress(u8, u8),
     KeyRelease(u8, u8),
-    SetAnimation(AnimationSync),
-    SyncAnimation(AnimationSync),
+    /// Along with when the animation started
+    SetAnimation(AnimationSync, SharedInstant),
+    SyncAnimation(AnimationSync, SharedInstant),
+    SyncBrightness(Brightness),
+    SyncIdle(bool),
+    SyncSuspended(bool),
     SyncMouseState(MouseState),
+    SyncLayer(u8),
+    SyncModifiers(Modifiers),
+    SyncHeldKeys(HeldKeys),
+    SyncHostOs(Option<HostOs>),
+    /// Sent when the link comes up, asks the primary side to push its state
+    RequestResync,
+    /// Asks the other side to switch the link to the given baud rate
+    ProposeBaud(u32),
+    /// Sent just before switching to a proposed baud rate
+    AcceptBaud(u32),
+    AnnounceRole(RoleAnnouncement),
 }
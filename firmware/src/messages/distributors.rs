@@ -15,17 +15,41 @@ pub async fn from_usb_distributor() {
         let msg = sub.next_message_pure().await;
 
         if msg.targets_side(side::get_side()) {
-            #[allow(unreachable_code)]
             handle_from_host(msg.msg.clone()).await;
         }
         if msg.targets_side(side::get_other_side()) {
-            interboard::send_msg(reliable_msg(DeviceToDevice::ForwardedFromHost(msg.msg)), 2).await;
+            // led frames are soon replaced by the next, no use retrying them
+            let msg = match msg.msg {
+                msg @ HostToDeviceMsg::DirectLeds(_) => {
+                    unreliable_msg(DeviceToDevice::ForwardedFromHost(msg))
+                }
+                msg => reliable_msg(DeviceToDevice::ForwardedFromHost(msg)),
+            };
+            interboard::send_msg(msg, 2).await;
         }
     }
 }
 
 async fn handle_from_host(msg: HostToDeviceMsg) {
-    match msg {}
+    match msg {
+        HostToDeviceMsg::FirmwareUpdate(update) => crate::dfu::handle(update).await,
+        HostToDeviceMsg::SetAnimation(settings) => crate::rgb::settings::update(settings).await,
+        HostToDeviceMsg::SetBrightness(level) => {
+            crate::rgb::brightness::set(crate::rgb::brightness::Brightness { level, on: true })
+                .await
+        }
+        HostToDeviceMsg::SetIdleTimeout(secs) => crate::idle::set_timeout(secs),
+        HostToDeviceMsg::RequestKeyCounts => crate::metrics::send_key_counts().await,
+        HostToDeviceMsg::DirectLeds(leds) => crate::rgb::direct::write(leds.offset, &leds.colours),
+        HostToDeviceMsg::SetPowerBudget(budget) => crate::rgb::power_budget::set(budget),
+        HostToDeviceMsg::EditPlaylist(edit) => crate::rgb::playlist::edit(edit).await,
+        HostToDeviceMsg::UploadScript(upload) => crate::rgb::scripts::handle(upload).await,
+        HostToDeviceMsg::Calibrate(edit) => crate::rgb::calibration::edit(edit),
+        HostToDeviceMsg::SetHostOs(os) => crate::host_os::set(os).await,
+        HostToDeviceMsg::EditSnippet(edit) => crate::keys::snippets::edit(edit),
+        HostToDeviceMsg::SetHostLayout(layout) => crate::keys::host_layout::set(layout),
+        HostToDeviceMsg::SetWindowsUnicode(mode) => crate::keys::unicode::set_windows_mode(mode),
+    }
 }
 
 #[embassy_executor::task]
@@ -39,12 +63,19 @@ pub async fn from_other_side_distributor() {
         // crate::log::info!("got msg: {:?}", msg);
 
         match msg {
-            DeviceToDevice::Ping => {
+            DeviceToDevice::Ping(ping_sent) => {
                 // log::info!("Got a ping");
-                interboard::send_msg(reliable_msg(DeviceToDevice::Pong), 3).await;
+                let pong = DeviceToDevice::Pong {
+                    ping_sent,
+                    received: interboard::clock::local_ticks(),
+                };
+                let _ = interboard::try_send_msg(unreliable_msg(pong), 3);
             }
-            DeviceToDevice::Pong => {
-                // log::info!("Got a pong");
+            DeviceToDevice::Pong {
+                ping_sent,
+                received,
+            } => {
+                interboard::clock::pong(ping_sent, received);
             }
             DeviceToDevice::ForwardedToHost(msg) => {
                 usb::send_msg(unreliable_msg(msg)).await;
@@ -63,43 +94,30 @@ pub enum MessageProvenance {
     Forwarded,
 }
 
-pub async fn send_to_host(
-    TransmittedMessage { msg, timeout }: TransmittedMessage<DeviceToHostMsg>,
-    provenance: MessageProvenance,
-) {
-    let side = side::get_side();
-    let msg = DeviceToHost {
-        from_side: side,
-        msg,
-    };
-    if side::this_side_has_usb() {
-        let msg = TransmittedMessage { msg, timeout };
+pub async fn send_to_host(msg: TransmittedMessage<DeviceToHostMsg>, provenance: MessageProvenance) {
+    let from_side = side::get_side();
+    let msg = msg.map(|msg| DeviceToHost { from_side, msg });
+    if side::is_primary() {
         usb::send_msg(msg).await;
     } else if provenance == MessageProvenance::Origin {
-        let msg = DeviceToDevice::ForwardedToHost(msg);
-        let msg = TransmittedMessage { msg, timeout };
+        let msg = msg.map(DeviceToDevice::ForwardedToHost);
         interboard::send_msg(msg, 3).await;
     }
 }
 
 pub fn try_send_to_host(
-    TransmittedMessage { msg, timeout }: TransmittedMessage<DeviceToHostMsg>,
+    msg: TransmittedMessage<DeviceToHostMsg>,
     provenance: MessageProvenance,
 ) -> Option<()> {
-    let side = side::get_side();
-    let msg = DeviceToHost {
-        from_side: side,
-        msg,
-    };
-    if side::this_side_has_usb() {
-        let msg = TransmittedMessage { msg, timeout };
+    let from_side = side::get_side();
+    let msg = msg.map(|msg| DeviceToHost { from_side, msg });
+    if side::is_primary() {
         usb::try_send_msg(msg).ok()
     } else if provenance == MessageProvenance::Origin {
-        let msg = DeviceToDevice::ForwardedToHost(msg);
-        let msg = TransmittedMessage { msg, timeout };
+        let msg = msg.map(DeviceToDevice::ForwardedToHost);
         interboard::try_send_msg(msg, 3).ok()
     } else {
-        // if we get here it means both sides have no usb connection
+        // if we get here it means neither side is primary
         Some(())
     }
 }
@@ -3,6 +3,7 @@ use embassy_time::Duration;
 
 pub mod device_to_device;
 pub mod distributors;
+pub mod resync;
 pub mod transmissions;
 
 pub use distributors::{send_to_host, try_send_to_host};
@@ -10,18 +11,33 @@ pub use distributors::{send_to_host, try_send_to_host};
 pub fn init(spawner: &Spawner) {
     spawner.must_spawn(distributors::from_usb_distributor());
     spawner.must_spawn(distributors::from_other_side_distributor());
+    spawner.must_spawn(resync::resync_task());
 }
 
 #[derive(Debug)]
 pub struct TransmittedMessage<T> {
     pub msg: T,
     pub timeout: Option<Duration>,
+    /// How many times a reliable message is resent before giving up on it, if
+    /// None it is resent until it gets through
+    pub max_retries: Option<u8>,
+}
+
+impl<T> TransmittedMessage<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> TransmittedMessage<U> {
+        TransmittedMessage {
+            msg: f(self.msg),
+            timeout: self.timeout,
+            max_retries: self.max_retries,
+        }
+    }
 }
 
 pub fn low_latency_msg<T>(msg: T) -> TransmittedMessage<T> {
     TransmittedMessage {
         msg,
         timeout: Some(Duration::from_micros(500)),
+        max_retries: None,
     }
 }
 
@@ -29,9 +45,14 @@ pub fn reliable_msg<T>(msg: T) -> TransmittedMessage<T> {
     TransmittedMessage {
         msg,
         timeout: Some(Duration::from_millis(5)),
+        max_retries: None,
     }
 }
 
 pub fn unreliable_msg<T>(msg: T) -> TransmittedMessage<T> {
-    TransmittedMessage { msg, timeout: None }
+    TransmittedMessage {
+        msg,
+        timeout: None,
+        max_retries: None,
+    }
 }
@@ -0,0 +1,98 @@
+use embassy_futures::select::{select3, Either3};
+
+use crate::{
+    host_os, idle,
+    interboard::{self, link::LinkState, THIS_SIDE_MESSAGE_BUS},
+    keys, power, rgb,
+    side::{self, Role},
+    utils::log,
+};
+
+use super::{device_to_device::DeviceToDevice, reliable_msg};
+
+/// Push everything the secondary side can't know about on its own
+async fn push_snapshot() {
+    log::info!("Pushing state to the other side");
+
+    let msgs = [
+        DeviceToDevice::SyncMouseState(keys::current_mouse_state()),
+        DeviceToDevice::SyncLayer(keys::current_layer()),
+        DeviceToDevice::SyncModifiers(keys::current_modifiers()),
+        DeviceToDevice::SyncBrightness(rgb::brightness::current()),
+        DeviceToDevice::SyncIdle(idle::is_idle()),
+        DeviceToDevice::SyncSuspended(power::is_suspended()),
+        DeviceToDevice::SyncHostOs(host_os::overridden()),
+    ];
+
+    for msg in msgs {
+        interboard::send_msg(reliable_msg(msg), 2).await;
+    }
+
+    rgb::send_cmd(rgb::Command::Resync).await;
+}
+
+/// Brings both sides back into agreement whenever the link comes (back) up
+///
+/// The primary side owns the shared state and pushes a snapshot of it, the
+/// other side asks for one in case it was the one that went away (a quick
+/// reboot may not be noticed as a link drop by the primary side). A side that
+/// takes over as primary also pushes its state, and one that steps down asks
+/// for the new primary's.
+#[embassy_executor::task]
+pub async fn resync_task() {
+    let mut link_sub = interboard::link::LINK_STATE_CHANGES.subscriber().unwrap();
+    let mut role_sub = side::ROLE_CHANGES.subscriber().unwrap();
+    let mut sub = THIS_SIDE_MESSAGE_BUS.subscriber().unwrap();
+
+    loop {
+        match select3(
+            link_sub.next_message_pure(),
+            sub.next_message_pure(),
+            role_sub.next_message_pure(),
+        )
+        .await
+        {
+            Either3::First(LinkState::Up) => {
+                if side::is_primary() {
+                    push_snapshot().await;
+                } else {
+                    interboard::send_msg(reliable_msg(DeviceToDevice::RequestResync), 2).await;
+                }
+            }
+            Either3::Second(DeviceToDevice::RequestResync) => {
+                if side::is_primary() {
+                    push_snapshot().await;
+                }
+            }
+            Either3::Second(DeviceToDevice::SyncMouseState(state)) => {
+                if !side::is_primary() {
+                    keys::set_current_mouse_state(state);
+                }
+            }
+            Either3::Second(DeviceToDevice::SyncLayer(layer)) => {
+                if !side::is_primary() {
+                    keys::set_current_layer(layer);
+                }
+            }
+            Either3::Second(DeviceToDevice::SyncModifiers(modifiers)) => {
+                if !side::is_primary() {
+                    keys::set_current_modifiers(modifiers);
+                }
+            }
+            Either3::Second(DeviceToDevice::SyncHostOs(os)) => host_os::synced(os),
+            Either3::Third(Role::Primary) => {
+                if interboard::link::is_up() {
+                    push_snapshot().await;
+                }
+            }
+            Either3::Third(Role::Secondary) => {
+                // anything the new primary pushed while we still thought we
+                // were in charge was ignored
+                if interboard::link::is_up() {
+                    interboard::send_msg(reliable_msg(DeviceToDevice::RequestResync), 2).await;
+                }
+            }
+            _ => {}
+        }
+    }
+}
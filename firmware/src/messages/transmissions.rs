@@ -3,6 +3,7 @@ use embassy_futures::select;
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, signal::Signal};
 use embassy_time::{with_timeout, Duration};
 use futures::Future;
+use portable_atomic::{AtomicU32, Ordering};
 use postcard::accumulator::{CobsAccumulator, FeedResult};
 use serde::{de::DeserializeOwned, Serialize};
 use shared::cmd::{CmdOrAck, Command};
@@ -13,23 +14,56 @@ use super::TransmittedMessage;
 
 const BUF_SIZE: usize = 128;
 
+/// Counters describing the health of a transmission channel
+pub struct TransmissionStats {
+    pub received: AtomicU32,
+    pub retries: AtomicU32,
+    pub csum_failures: AtomicU32,
+    pub deser_errors: AtomicU32,
+    pub overfull: AtomicU32,
+    /// Reliable commands that were given up on after too many retries
+    pub gave_up: AtomicU32,
+}
+
+impl TransmissionStats {
+    pub const fn new() -> Self {
+        Self {
+            received: AtomicU32::new(0),
+            retries: AtomicU32::new(0),
+            csum_failures: AtomicU32::new(0),
+            deser_errors: AtomicU32::new(0),
+            overfull: AtomicU32::new(0),
+            gave_up: AtomicU32::new(0),
+        }
+    }
+
+    fn bump(counter: &AtomicU32) {
+        counter.add(1, Ordering::Relaxed);
+    }
+}
+
 struct EventSenderImpl<'e, T> {
     mix_chan: &'e Channel<ThreadModeRawMutex, CmdOrAck<T>, 16>,
     ack_signal: &'e Signal<ThreadModeRawMutex, ()>,
+    stats: &'e TransmissionStats,
 }
 
 pub trait EventSender<T> {
     async fn send(&self, cmd: TransmittedMessage<T>, id: u8) {
-        let TransmittedMessage { msg, timeout, .. } = cmd;
+        let TransmittedMessage {
+            msg,
+            timeout,
+            max_retries,
+        } = cmd;
         if let Some(timeout) = timeout {
-            let _ = self.send_reliable(msg, timeout, id).await;
+            let _ = self.send_reliable(msg, timeout, max_retries, id).await;
         } else {
             let _ = self.send_unreliable(msg, id).await;
         }
     }
 
     async fn send_unreliable(&self, cmd: T, id: u8);
-    async fn send_reliable(&self, cmd: T, timeout: Duration, id: u8);
+    async fn send_reliable(&self, cmd: T, timeout: Duration, max_retries: Option<u8>, id: u8);
 }
 
 struct EventOutProcessor<'e, Sent, TX> {
@@ -42,6 +76,7 @@ struct EventInProcessor<'e, Sent, RX, FnTx> {
     out_cb: FnTx,
     mix_chan: &'e Channel<ThreadModeRawMutex, CmdOrAck<Sent>, 16>,
     ack_signal: &'e Signal<ThreadModeRawMutex, ()>,
+    stats: &'e TransmissionStats,
 }
 
 impl<'e, Sent, RX, FnTx> EventInProcessor<'e, Sent, RX, FnTx>
@@ -71,9 +106,11 @@ where
                     FeedResult::Consumed => break 'cobs,
                     FeedResult::OverFull(buf) => {
                         // log::debug!("buffer overfull");
+                        TransmissionStats::bump(&self.stats.overfull);
                         buf
                     }
                     FeedResult::DeserError(buf) => {
+                        TransmissionStats::bump(&self.stats.deser_errors);
                         // log::debug!(
                         //     "Message decoder failed to deserialize a message of type {}: {:?}",
                         //     core::any::type_name::<CmdOrAck<Received>>(),
@@ -88,6 +125,7 @@ where
                             CmdOrAck::Cmd(c) => {
                                 if c.validate() {
                                     // log::info!("Hi I got a command: {}", c);
+                                    TransmissionStats::bump(&self.stats.received);
                                     if c.command_seq.reliable() {
                                         self.mix_chan.send(CmdOrAck::Ack).await;
                                     }
@@ -97,6 +135,7 @@ where
                                     }
                                 } else {
                                     // log::debug!("Corrupted parsed command: {:?}", c);
+                                    TransmissionStats::bump(&self.stats.csum_failures);
                                 }
                             }
                             CmdOrAck::Ack => {
@@ -150,7 +189,9 @@ impl<'a, T: Hash + Clone> EventSender<T> for EventSenderImpl<'a, T> {
         self.mix_chan.send(CmdOrAck::Cmd(cmd)).await;
     }
 
-    async fn send_reliable(&self, cmd: T, mut timeout: Duration, id: u8) {
+    async fn send_reliable(&self, cmd: T, mut timeout: Duration, max_retries: Option<u8>, id: u8) {
+        let mut retries = 0;
+
         loop {
             let cmd = Command::new_reliable(cmd.clone(), id);
             self.mix_chan.send(CmdOrAck::Cmd(cmd)).await;
@@ -162,6 +203,13 @@ impl<'a, T: Hash + Clone> EventSender<T> for EventSenderImpl<'a, T> {
                 return;
             }
 
+            if max_retries.is_some_and(|max| retries >= max) {
+                TransmissionStats::bump(&self.stats.gave_up);
+                return;
+            }
+
+            retries += 1;
+            TransmissionStats::bump(&self.stats.retries);
             timeout += Duration::from_micros(100);
         }
     }
@@ -172,6 +220,7 @@ pub async fn eventer<Sent, Received, TX, RX, FnRx, FnTx, FnRxFut, FnTxFut>(
     rx: RX,
     fn_rx: FnRx,
     fn_tx: FnTx,
+    stats: &TransmissionStats,
 ) where
     Sent: Hash + Clone + Serialize + WhichDebug,
     Received: Hash + Clone + DeserializeOwned + WhichDebug,
@@ -190,6 +239,7 @@ pub async fn eventer<Sent, Received, TX, RX, FnRx, FnTx, FnRxFut, FnTxFut>(
     let sender = EventSenderImpl {
         mix_chan: &mix_chan,
         ack_signal: &ack_signal,
+        stats,
     };
 
     let mut out_processor = EventOutProcessor::<Sent, TX> {
@@ -202,6 +252,7 @@ pub async fn eventer<Sent, Received, TX, RX, FnRx, FnTx, FnRxFut, FnTxFut>(
         out_cb: fn_tx,
         mix_chan: &mix_chan,
         ack_signal: &ack_signal,
+        stats,
     };
 
     let sender_proc = async {
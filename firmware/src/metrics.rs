@@ -1,11 +1,20 @@
 use core::num::Wrapping;
 
 use embassy_executor::Spawner;
-use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, pubsub::PubSubChannel};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex, channel::Channel, mutex::Mutex, pubsub::PubSubChannel,
+};
 use embassy_time::Duration;
+use portable_atomic::{AtomicU32, Ordering};
 use serde::{Deserialize, Serialize};
+use shared::device_to_host::DeviceToHostMsg;
 
-use crate::{flash, keys::KEY_EVENTS, utils};
+use crate::{
+    flash,
+    keys::matrix::{COLS, ROWS},
+    messages::{distributors::MessageProvenance, reliable_msg, send_to_host},
+    utils,
+};
 
 static CURRENT_METRICS: Mutex<ThreadModeRawMutex, Metrics> = Mutex::new(Metrics::default());
 
@@ -25,6 +34,62 @@ impl Metrics {
     }
 }
 
+/// How many times each key has been pressed, indexed by row then column
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+struct KeyCounts([[u32; COLS]; ROWS]);
+
+/// Presses of the keys themselves, not the ones replayed when the sides
+/// reconcile what's held
+static KEY_PRESSES: Channel<ThreadModeRawMutex, (u8, u8), 8> = Channel::new();
+
+pub async fn key_pressed(row: u8, col: u8) {
+    KEY_PRESSES.send((row, col)).await;
+}
+
+static KEY_COUNTS: [[AtomicU32; COLS]; ROWS] =
+    [const { [const { AtomicU32::new(0) }; COLS] }; ROWS];
+
+impl KeyCounts {
+    fn current() -> Self {
+        Self(core::array::from_fn(|row| {
+            core::array::from_fn(|col| KEY_COUNTS[row][col].load(Ordering::Relaxed))
+        }))
+    }
+
+    fn restore(&self) {
+        for (counts, row) in self.0.iter().zip(&KEY_COUNTS) {
+            for (&count, counter) in counts.iter().zip(row) {
+                counter.store(count, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// How many times the key at a matrix position has been pressed, on either
+/// side
+pub fn key_count(row: u8, col: u8) -> u32 {
+    KEY_COUNTS
+        .get(row as usize)
+        .and_then(|r| r.get(col as usize))
+        .map_or(0, |c| c.load(Ordering::Relaxed))
+}
+
+/// Send the per key press counts to the host, a row at a time
+pub async fn send_key_counts() {
+    let KeyCounts(counts) = KeyCounts::current();
+
+    for (row, counts) in counts.into_iter().enumerate() {
+        send_to_host(
+            reliable_msg(DeviceToHostMsg::KeyCounts {
+                row: row as u8,
+                counts,
+            }),
+            MessageProvenance::Origin,
+        )
+        .await;
+    }
+}
+
 pub async fn init(spawner: &Spawner) {
     if let Some(m) = flash::get::<Metrics>().await {
         utils::log::info!("Loaded up metrics with: {:?}", m);
@@ -32,6 +97,10 @@ pub async fn init(spawner: &Spawner) {
         push_update(m);
     }
 
+    if let Some(counts) = flash::get::<KeyCounts>().await {
+        counts.restore();
+    }
+
     spawner.must_spawn(metrics_syncer());
     spawner.must_spawn(key_counter());
 }
@@ -47,12 +116,13 @@ pub async fn request_sync() {
 
 #[embassy_executor::task]
 async fn key_counter() {
-    let mut sub = KEY_EVENTS.subscriber().unwrap();
-
     loop {
-        let k = sub.next_message_pure().await;
-        if !k.is_press() {
-            continue;
+        let (row, col) = KEY_PRESSES.receive().await;
+        if let Some(counter) = KEY_COUNTS
+            .get(row as usize)
+            .and_then(|r| r.get(col as usize))
+        {
+            counter.add(1, Ordering::Relaxed);
         }
 
         let mut m = CURRENT_METRICS.lock().await;
@@ -66,6 +136,7 @@ async fn key_counter() {
 async fn metrics_syncer() {
     let mut tick = embassy_time::Ticker::every(Duration::from_secs(60 * 5));
     let mut last = Metrics::default();
+    let mut last_counts = KeyCounts::current();
 
     loop {
         tick.next().await;
@@ -79,5 +150,13 @@ async fn metrics_syncer() {
         }
 
         last = current;
+
+        let counts = KeyCounts::current();
+
+        if counts != last_counts {
+            let _ = flash::set(&counts).await;
+        }
+
+        last_counts = counts;
     }
 }
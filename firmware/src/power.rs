@@ -0,0 +1,119 @@
+//! Following the host in and out of suspend
+//!
+//! The primary side watches its usb bus for the host suspending and tells the
+//! other side, both then pause their leds and display until the host resumes.
+//! Pressing a key while suspended asks the host to wake up.
+
+use embassy_executor::Spawner;
+use embassy_futures::select::{select4, Either4};
+use embassy_sync::{
+    blocking_mutex::raw::ThreadModeRawMutex, pubsub::PubSubChannel, signal::Signal,
+};
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::{
+    interboard::{self, THIS_SIDE_MESSAGE_BUS},
+    keys::KEY_EVENTS,
+    messages::{device_to_device::DeviceToDevice, reliable_msg},
+    side::{self, Role},
+    usb,
+    utils::log,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub enum PowerState {
+    Awake,
+    Suspended,
+}
+
+/// Published to whenever this side is suspended or resumed
+pub static POWER_CHANGES: PubSubChannel<ThreadModeRawMutex, PowerState, 1, 4, 1> =
+    PubSubChannel::new();
+
+/// Whether the keyboard as a whole is suspended
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the host on this side's usb has suspended us
+static USB_SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+static USB_SUSPEND_CHANGED: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+pub fn init(spawner: &Spawner) {
+    spawner.must_spawn(power_task());
+}
+
+pub fn is_suspended() -> bool {
+    SUSPENDED.load(Ordering::Relaxed)
+}
+
+pub async fn wait_for_resume() {
+    let mut sub = POWER_CHANGES.subscriber().unwrap();
+
+    while is_suspended() {
+        sub.next_message_pure().await;
+    }
+}
+
+fn set_suspended(suspended: bool) {
+    if SUSPENDED.swap(suspended, Ordering::Relaxed) != suspended {
+        log::info!("Suspended? {}", suspended);
+
+        let state = if suspended {
+            PowerState::Suspended
+        } else {
+            PowerState::Awake
+        };
+        POWER_CHANGES.immediate_publisher().publish_immediate(state);
+    }
+}
+
+/// Called by the usb device when the host suspends or resumes the bus
+pub(crate) fn usb_suspended(suspended: bool) {
+    USB_SUSPENDED.store(suspended, Ordering::Relaxed);
+    USB_SUSPEND_CHANGED.signal(());
+}
+
+/// Take on this side's usb state, if we're the one talking to the host
+async fn follow_usb() {
+    if !side::is_primary() {
+        return;
+    }
+
+    let suspended = USB_SUSPENDED.load(Ordering::Relaxed);
+    set_suspended(suspended);
+    interboard::send_msg(reliable_msg(DeviceToDevice::SyncSuspended(suspended)), 3).await;
+}
+
+#[embassy_executor::task]
+async fn power_task() {
+    let mut key_sub = KEY_EVENTS.subscriber().unwrap();
+    let mut role_sub = side::ROLE_CHANGES.subscriber().unwrap();
+    let mut sub = THIS_SIDE_MESSAGE_BUS.subscriber().unwrap();
+
+    loop {
+        match select4(
+            USB_SUSPEND_CHANGED.wait(),
+            sub.next_message_pure(),
+            role_sub.next_message_pure(),
+            key_sub.next_message_pure(),
+        )
+        .await
+        {
+            Either4::First(()) => follow_usb().await,
+            Either4::Second(DeviceToDevice::SyncSuspended(suspended)) => {
+                if !side::is_primary() {
+                    set_suspended(suspended);
+                }
+            }
+            Either4::Second(_) => {}
+            Either4::Third(Role::Primary) => follow_usb().await,
+            Either4::Third(Role::Secondary) => {}
+            Either4::Fourth(evt) => {
+                if evt.is_press() && side::is_primary() && USB_SUSPENDED.load(Ordering::Relaxed) {
+                    usb::wake_host();
+                }
+            }
+        }
+    }
+}
@@ -2,6 +2,7 @@ use core::{fmt::Debug, hash::Hash};
 
 use cichlid::ColorRGB;
 use embassy_time::Duration;
+use keyberon::layout::Event;
 use serde::{de::DeserializeOwned, Serialize};
 
 use super::layout::Light;
@@ -13,6 +14,9 @@ pub trait Animation {
     fn tick(&mut self);
     fn render(&self, light: &Light) -> ColorRGB;
 
+    /// Called for every key press and release, on either side
+    fn key_event(&mut self, _event: Event) {}
+
     fn construct_sync(&self) -> Self::SyncMessage;
     fn sync(&mut self, sync: Self::SyncMessage);
     fn new_from_sync(sync: Self::SyncMessage) -> Self;
@@ -0,0 +1,88 @@
+use cichlid::ColorRGB;
+use embassy_time::Duration;
+use fixed::types::{I4F12, U0F16, U16F16};
+use fixed_macro::fixed;
+
+use crate::{
+    keys::matrix::{COLS, ROWS},
+    metrics,
+    rgb::{
+        animation::Animation,
+        layout::Light,
+        math_utils::{ease_fade, rainbow},
+    },
+};
+
+/// Colours each switch by how often it's been pressed, from blue for the
+/// least used keys to red for the most
+pub struct Heatmap {
+    heat: [[U0F16; COLS]; ROWS],
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        let mut heatmap = Self {
+            heat: Default::default(),
+        };
+        heatmap.tick();
+        heatmap
+    }
+}
+
+impl Animation for Heatmap {
+    type SyncMessage = ();
+
+    fn tick_rate(&self) -> Duration {
+        Duration::from_hz(1)
+    }
+
+    fn tick(&mut self) {
+        let mut max = 1;
+        for row in 0..ROWS {
+            for col in 0..COLS {
+                max = max.max(metrics::key_count(row as u8, col as u8));
+            }
+        }
+
+        let max = U16F16::saturating_from_num(max);
+
+        for (row, heat) in self.heat.iter_mut().enumerate() {
+            for (col, heat) in heat.iter_mut().enumerate() {
+                let count = U16F16::saturating_from_num(metrics::key_count(row as u8, col as u8));
+                *heat = (count / max).saturating_to_num();
+            }
+        }
+    }
+
+    fn render(&self, light: &Light) -> ColorRGB {
+        let Some((col, row)) = light.position else {
+            return ColorRGB::Black;
+        };
+
+        let Some(&heat) = self
+            .heat
+            .get(row as usize)
+            .and_then(|r| r.get(col as usize))
+        else {
+            return ColorRGB::Black;
+        };
+
+        // red is at zero, blue two thirds of the way round
+        let hue = fixed!(0.666: I4F12) * (I4F12::ONE - heat.to_num::<I4F12>());
+        let mut colour = rainbow(hue);
+
+        // keep unused keys faintly lit so the layout is still visible
+        let level = ease_fade(heat).max(40);
+        colour.scale(level);
+
+        colour
+    }
+
+    fn construct_sync(&self) -> Self::SyncMessage {}
+
+    fn sync(&mut self, _sync: Self::SyncMessage) {}
+
+    fn new_from_sync(_sync: Self::SyncMessage) -> Self {
+        Self::default()
+    }
+}
@@ -1,21 +1,29 @@
 use cichlid::ColorRGB;
 use embassy_time::Duration;
-use rand::seq::SliceRandom;
+use keyberon::layout::Event;
+use rand::{seq::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
+use shared::host_to_device::{AnimationChoice, Colour, PerlinColours};
 
 use crate::rng::MyRng;
 
 use super::{animation::Animation, layout::Light};
 
+pub mod heatmap;
 pub mod null;
 pub mod perlin;
 pub mod rain;
+pub mod script;
 pub mod snow;
+pub mod typing;
 
 pub enum DynAnimation {
     Snow(snow::Snow),
     Perlin(perlin::Perlin),
     Rain(rain::Rain),
+    Typing(typing::Typing),
+    Heatmap(heatmap::Heatmap),
+    Script(script::Script),
     Null(null::Null),
 }
 
@@ -67,6 +75,14 @@ macro_rules! dyn_impl {
                 }
             }
 
+            fn key_event(&mut self, event: Event) {
+                match self {
+                    $(
+                        Self::$variant(x) => x.key_event(event)
+                    ),+
+                }
+            }
+
             fn sync(&mut self, sync: Self::SyncMessage) {
                 #[allow(unreachable_patterns)]
                 match (self, sync) {
@@ -94,6 +110,9 @@ dyn_impl!(
     [Snow, snow::Snow],
     [Perlin, perlin::Perlin],
     [Rain, rain::Rain],
+    [Typing, typing::Typing],
+    [Heatmap, heatmap::Heatmap],
+    [Script, script::Script],
     [Null, null::Null]
 );
 
@@ -113,6 +132,47 @@ pub enum AnimationSync {
     Rain(
         #[cfg_attr(feature = "probe", defmt(Debug2Format))] <rain::Rain as Animation>::SyncMessage,
     ),
+    Typing(
+        #[cfg_attr(feature = "probe", defmt(Debug2Format))]
+        <typing::Typing as Animation>::SyncMessage,
+    ),
+    Heatmap(
+        #[cfg_attr(feature = "probe", defmt(Debug2Format))]
+        <heatmap::Heatmap as Animation>::SyncMessage,
+    ),
+    Script(
+        #[cfg_attr(feature = "probe", defmt(Debug2Format))]
+        <script::Script as Animation>::SyncMessage,
+    ),
+}
+
+fn colour(c: Colour) -> ColorRGB {
+    ColorRGB::new(c.r, c.g, c.b)
+}
+
+impl From<AnimationChoice> for AnimationSync {
+    fn from(choice: AnimationChoice) -> Self {
+        match choice {
+            AnimationChoice::Off => AnimationSync::Null(()),
+            AnimationChoice::Snow => AnimationSync::Snow(MyRng.gen()),
+            AnimationChoice::Perlin(colours) => {
+                let mode = match colours {
+                    PerlinColours::Random => perlin::ColourMode::Random,
+                    PerlinColours::Single(c) => perlin::ColourMode::Single(colour(c)),
+                    PerlinColours::Double(a, b) => perlin::ColourMode::Double(colour(a), colour(b)),
+                };
+                AnimationSync::Perlin((mode, MyRng.gen()))
+            }
+            AnimationChoice::Rain { colour: c } => {
+                AnimationSync::Rain((c.map(colour), MyRng.gen()))
+            }
+            AnimationChoice::Typing { colour: c } => {
+                AnimationSync::Typing((c.map(colour), MyRng.gen()))
+            }
+            AnimationChoice::Heatmap => AnimationSync::Heatmap(()),
+            AnimationChoice::Script => AnimationSync::Script(script::installed_hash().unwrap_or(0)),
+        }
+    }
 }
 
 trait WrapAnimationSync {
@@ -133,3 +193,6 @@ wrap_sync!(snow::Snow, AnimationSync::Snow);
 wrap_sync!(perlin::Perlin, AnimationSync::Perlin);
 wrap_sync!(null::Null, AnimationSync::Null);
 wrap_sync!(rain::Rain, AnimationSync::Rain);
+wrap_sync!(typing::Typing, AnimationSync::Typing);
+wrap_sync!(heatmap::Heatmap, AnimationSync::Heatmap);
+wrap_sync!(script::Script, AnimationSync::Script);
@@ -9,7 +9,7 @@ use rand::{rngs::SmallRng, Rng, SeedableRng};
 use crate::{
     rgb::{
         animation::Animation,
-        math_utils::{ease_fade, rand_rainbow, wrapping_delta_u},
+        math_utils::{ease_fade, rand_rainbow, seeded_rainbow, sqrt, wrapping_delta_u},
     },
     rng::{splitmix64, MyRng},
 };
@@ -22,6 +22,7 @@ struct Splash {
 }
 
 pub struct Rain {
+    seed: u8,
     tick: U16F16,
     rng: SmallRng,
     colour: Option<ColorRGB>,
@@ -33,20 +34,13 @@ const BOUNDS: (Range<i16>, Range<i16>) = (-20..100, -30..70);
 
 impl Default for Rain {
     fn default() -> Self {
-        let seed: u8 = MyRng.gen();
-
         let colour = if MyRng.gen_bool(0.2) {
             None
         } else {
             Some(rand_rainbow())
         };
 
-        Self {
-            tick: Default::default(),
-            rng: SmallRng::seed_from_u64(splitmix64(seed as u64)),
-            splashes: Default::default(),
-            colour,
-        }
+        Self::new_from_sync((colour, MyRng.gen()))
     }
 }
 
@@ -55,7 +49,9 @@ fn tick_delta(a: U16F16, b: U16F16) -> U16F16 {
 }
 
 impl Animation for Rain {
-    type SyncMessage = Option<ColorRGB>;
+    /// The colour, if there's just the one, and the seed so that both sides
+    /// place the same splashes
+    type SyncMessage = (Option<ColorRGB>, u8);
 
     fn tick_rate(&self) -> embassy_time::Duration {
         Duration::from_hz(60)
@@ -83,7 +79,7 @@ impl Animation for Rain {
                 x: I16F16::from_num(x),
                 y: I16F16::from_num(y),
                 instant: self.tick,
-                colour: self.colour.unwrap_or_else(|| rand_rainbow()),
+                colour: self.colour.unwrap_or_else(|| seeded_rainbow(&mut self.rng)),
             };
             let _ = self.splashes.push_front(splash);
         }
@@ -106,9 +102,7 @@ impl Animation for Rain {
             let dy = splash.y.dist(yy);
 
             let dist = dx * dx + dy * dy;
-            let dist = I16F16::from_num(embassy_rp::rom_data::float_funcs::fsqrt(
-                dist.to_num::<f32>(),
-            ));
+            let dist = I16F16::from_num(sqrt(dist.to_num::<f32>()));
 
             let time_delta = tick_delta(self.tick, splash.instant).saturating_to_num::<I16F16>();
 
@@ -144,17 +138,20 @@ impl Animation for Rain {
     }
 
     fn construct_sync(&self) -> Self::SyncMessage {
-        self.colour
+        (self.colour, self.seed)
     }
 
-    fn sync(&mut self, sync: Self::SyncMessage) {
-        self.colour = sync;
+    fn sync(&mut self, (colour, _): Self::SyncMessage) {
+        self.colour = colour;
     }
 
-    fn new_from_sync(sync: Self::SyncMessage) -> Self {
+    fn new_from_sync((colour, seed): Self::SyncMessage) -> Self {
         Self {
-            colour: sync,
-            ..Self::default()
+            seed,
+            tick: Default::default(),
+            rng: SmallRng::seed_from_u64(splitmix64(seed as u64)),
+            splashes: Default::default(),
+            colour,
         }
     }
 }
@@ -0,0 +1,289 @@
+use core::cell::RefCell;
+
+use cichlid::ColorRGB;
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use embassy_time::Duration;
+use fixed::types::{I16F16, I4F12};
+use fixed_macro::fixed;
+use keyberon::layout::Event;
+use shared::script::{self, Op, ProgramError, MAX_PROGRAM_LEN, MAX_STACK};
+
+use crate::{
+    keys::matrix::{COLS, ROWS},
+    rgb::{
+        animation::Animation,
+        layout::{Kind, Light},
+        math_utils::rainbow,
+    },
+    rng::splitmix64,
+};
+
+#[derive(Clone)]
+pub struct Program {
+    code: heapless::Vec<u8, MAX_PROGRAM_LEN>,
+    hash: u32,
+}
+
+impl Program {
+    fn tick_rate(&self) -> u8 {
+        self.code[0]
+    }
+}
+
+/// The program uploaded by the host
+static INSTALLED: Mutex<ThreadModeRawMutex, RefCell<Option<Program>>> =
+    Mutex::new(RefCell::new(None));
+
+/// Check over a program and use it from now on, returning its hash
+pub fn install(code: &[u8]) -> Result<u32, ProgramError> {
+    script::validate(code)?;
+
+    let program = Program {
+        code: heapless::Vec::from_slice(code).map_err(|_| ProgramError::TooLong)?,
+        hash: script::hash(code),
+    };
+    let hash = program.hash;
+
+    INSTALLED.lock(|p| *p.borrow_mut() = Some(program));
+
+    Ok(hash)
+}
+
+pub fn installed_hash() -> Option<u32> {
+    INSTALLED.lock(|p| p.borrow().as_ref().map(|p| p.hash))
+}
+
+/// Runs a program from the host for each light, see [`shared::script`]
+///
+/// Both sides need to have been sent the same program, a side that doesn't
+/// have it stays dark.
+pub struct Script {
+    program: Option<Program>,
+    ticks: u32,
+    held: [[bool; COLS]; ROWS],
+    last_press: [[Option<u32>; COLS]; ROWS],
+}
+
+impl Script {
+    fn hash(&self) -> u32 {
+        self.program.as_ref().map_or(0, |p| p.hash)
+    }
+
+    /// Seconds covered by some number of ticks
+    fn seconds(&self, ticks: u32) -> I16F16 {
+        let Some(program) = &self.program else {
+            return I16F16::ZERO;
+        };
+
+        let hz = program.tick_rate() as u32;
+        let whole = I16F16::saturating_from_num(ticks / hz);
+        let part = I16F16::from_num(ticks % hz) / I16F16::from_num(hz);
+
+        whole.saturating_add(part)
+    }
+
+    fn key_state(&self, light: &Light) -> Option<(bool, Option<u32>)> {
+        let (col, row) = light.position?;
+        let (row, col) = (row as usize, col as usize);
+
+        Some((*self.held.get(row)?.get(col)?, self.last_press[row][col]))
+    }
+
+    fn run(&self, light: &Light) -> Option<ColorRGB> {
+        let program = self.program.as_ref()?;
+        let mut stack = heapless::Vec::<I16F16, MAX_STACK>::new();
+
+        macro_rules! pop {
+            () => {
+                stack.pop()?
+            };
+        }
+
+        macro_rules! push {
+            ($v:expr) => {
+                stack.push($v).ok()?
+            };
+        }
+
+        fn truth(b: bool) -> I16F16 {
+            if b {
+                I16F16::ONE
+            } else {
+                I16F16::ZERO
+            }
+        }
+
+        let mut ops = &program.code[1..];
+
+        while let Some((&op, rest)) = ops.split_first() {
+            ops = rest;
+
+            match Op::from_u8(op)? {
+                Op::Const => {
+                    let (bytes, rest) = ops.split_first_chunk::<4>()?;
+                    ops = rest;
+                    push!(I16F16::from_le_bytes(*bytes));
+                }
+                Op::Time => {
+                    let hour = program.tick_rate() as u32 * 60 * 60;
+                    push!(self.seconds(self.ticks % hour));
+                }
+                Op::X => push!(I16F16::from_num(light.location.0)),
+                Op::Y => push!(I16F16::from_num(light.location.1)),
+                Op::Index => push!(I16F16::from_num(light.index)),
+                Op::IsSwitch => push!(truth(light.kind == Kind::Switch)),
+                Op::Pressed => push!(truth(self.key_state(light).is_some_and(|(held, _)| held))),
+                Op::SincePress => {
+                    let since = match self.key_state(light) {
+                        Some((_, Some(at))) => self.seconds(self.ticks.wrapping_sub(at)),
+                        _ => I16F16::MAX,
+                    };
+                    push!(since);
+                }
+                op @ (Op::Add
+                | Op::Sub
+                | Op::Mul
+                | Op::Div
+                | Op::Rem
+                | Op::Min
+                | Op::Max
+                | Op::Lt
+                | Op::Gt) => {
+                    let b = pop!();
+                    let a = pop!();
+
+                    push!(match op {
+                        Op::Add => a.saturating_add(b),
+                        Op::Sub => a.saturating_sub(b),
+                        Op::Mul => a.saturating_mul(b),
+                        Op::Div if b == I16F16::ZERO => I16F16::ZERO,
+                        Op::Div => a.saturating_div(b),
+                        Op::Rem => a.checked_rem(b).unwrap_or(I16F16::ZERO),
+                        Op::Min => a.min(b),
+                        Op::Max => a.max(b),
+                        Op::Lt => truth(a < b),
+                        _ => truth(a > b),
+                    });
+                }
+                Op::Neg => {
+                    let a = pop!();
+                    push!(a.saturating_neg());
+                }
+                Op::Abs => {
+                    let a = pop!();
+                    push!(a.saturating_abs());
+                }
+                Op::Floor => {
+                    let a = pop!();
+                    push!(a.checked_floor().unwrap_or(a));
+                }
+                Op::Fract => {
+                    let a = pop!();
+                    push!(a.frac());
+                }
+                Op::Sin => {
+                    // sin(2πx - π) = -sin(2πx), and keeps the angle in range
+                    let a = pop!();
+                    let angle = a.frac() * I16F16::TAU - I16F16::PI;
+                    push!(-cordic::sin(angle));
+                }
+                Op::Hash => {
+                    let a = pop!();
+                    let bits = splitmix64(a.to_bits() as u32 as u64) & 0xffff;
+                    push!(I16F16::from_bits(bits as i32));
+                }
+                Op::Hue => {
+                    let a = pop!();
+                    let colour = rainbow(a.frac().to_num::<I4F12>());
+
+                    for c in [colour.r, colour.g, colour.b] {
+                        push!(I16F16::from_num(c) / fixed!(255: I16F16));
+                    }
+                }
+                Op::Select => {
+                    let c = pop!();
+                    let b = pop!();
+                    let a = pop!();
+                    push!(if a != I16F16::ZERO { b } else { c });
+                }
+                Op::Dup => {
+                    let a = *stack.last()?;
+                    push!(a);
+                }
+                Op::Swap => {
+                    let b = pop!();
+                    let a = pop!();
+                    push!(b);
+                    push!(a);
+                }
+                Op::Drop => {
+                    pop!();
+                }
+            }
+        }
+
+        let level = |x: I16F16| {
+            (x.clamp(I16F16::ZERO, I16F16::ONE) * fixed!(255: I16F16)).saturating_to_num::<u8>()
+        };
+
+        let b = level(pop!());
+        let g = level(pop!());
+        let r = level(pop!());
+
+        Some(ColorRGB { r, g, b })
+    }
+}
+
+impl Animation for Script {
+    /// The hash of the program, the runner keeps both sides at the same tick
+    type SyncMessage = u32;
+
+    fn tick_rate(&self) -> Duration {
+        match &self.program {
+            Some(program) => Duration::from_hz(program.tick_rate() as u64),
+            None => Duration::from_hz(1),
+        }
+    }
+
+    fn tick(&mut self) {
+        self.ticks = self.ticks.wrapping_add(1);
+    }
+
+    fn render(&self, light: &Light) -> ColorRGB {
+        self.run(light).unwrap_or(ColorRGB::Black)
+    }
+
+    fn key_event(&mut self, event: Event) {
+        let (row, col, pressed) = match event {
+            Event::Press(row, col) => (row, col, true),
+            Event::Release(row, col) => (row, col, false),
+        };
+
+        let (row, col) = (row as usize, col as usize);
+        let Some(held) = self.held.get_mut(row).and_then(|r| r.get_mut(col)) else {
+            return;
+        };
+
+        *held = pressed;
+        if pressed {
+            self.last_press[row][col] = Some(self.ticks);
+        }
+    }
+
+    fn construct_sync(&self) -> Self::SyncMessage {
+        self.hash()
+    }
+
+    fn sync(&mut self, _sync: Self::SyncMessage) {}
+
+    fn new_from_sync(hash: Self::SyncMessage) -> Self {
+        let program = INSTALLED.lock(|p| p.borrow().clone().filter(|p| p.hash == hash));
+
+        Self {
+            program,
+            ticks: 0,
+            held: Default::default(),
+            last_press: Default::default(),
+        }
+    }
+}
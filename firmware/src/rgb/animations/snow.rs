@@ -12,7 +12,7 @@ use crate::{
         self,
         animation::Animation,
         layout::NUM_COLS,
-        math_utils::{ease_fade, rand_rainbow, wrapping_delta_u},
+        math_utils::{ease_fade, seeded_rainbow, wrapping_delta_u},
     },
     rng::{splitmix64, MyRng},
     side::get_side,
@@ -27,6 +27,7 @@ struct Snowflake {
 }
 
 pub struct Snow {
+    seed: u8,
     tick: U16F16,
     rng: SmallRng,
     snowflakes: heapless::Deque<Snowflake, 16>,
@@ -38,14 +39,7 @@ const Y_BOUNDS: Range<i16> = -30..90;
 
 impl Default for Snow {
     fn default() -> Self {
-        let seed: u8 = MyRng.gen();
-
-        Self {
-            tick: Default::default(),
-            rng: SmallRng::seed_from_u64(splitmix64(seed as u64)),
-            snowflakes: Default::default(),
-            column_weights: Default::default(),
-        }
+        Self::new_from_sync(MyRng.gen())
     }
 }
 
@@ -71,7 +65,8 @@ fn weighted_iter(
 }
 
 impl Animation for Snow {
-    type SyncMessage = ();
+    /// The seed, both sides place the same snowflakes
+    type SyncMessage = u8;
 
     fn tick_rate(&self) -> embassy_time::Duration {
         Duration::from_hz(60)
@@ -116,7 +111,7 @@ impl Animation for Snow {
                 x: I16F16::from_num(x),
                 y: I16F16::from_num(Y_BOUNDS.end),
                 instant: self.tick,
-                colour: rand_rainbow(),
+                colour: seeded_rainbow(&mut self.rng),
             };
 
             if self.snowflakes.push_front(snowflake).is_ok() {
@@ -157,12 +152,18 @@ impl Animation for Snow {
     }
 
     fn construct_sync(&self) -> Self::SyncMessage {
-        ()
+        self.seed
     }
 
     fn sync(&mut self, _sync: Self::SyncMessage) {}
 
-    fn new_from_sync(_sync: Self::SyncMessage) -> Self {
-        Self::default()
+    fn new_from_sync(seed: Self::SyncMessage) -> Self {
+        Self {
+            seed,
+            tick: Default::default(),
+            rng: SmallRng::seed_from_u64(splitmix64(seed as u64)),
+            snowflakes: Default::default(),
+            column_weights: Default::default(),
+        }
     }
 }
@@ -0,0 +1,180 @@
+use cichlid::ColorRGB;
+use embassy_time::Duration;
+use fixed::types::{I16F16, U0F16, U16F16};
+use fixed_macro::fixed;
+use keyberon::layout::Event;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    rgb::{
+        animation::Animation,
+        layout::{self, Light},
+        math_utils::{ease_fade, rand_rainbow, seeded_rainbow, sqrt, wrapping_delta_u},
+    },
+    rng::{splitmix64, MyRng},
+};
+
+struct Ripple {
+    x: I16F16,
+    y: I16F16,
+    instant: U16F16,
+    colour: ColorRGB,
+}
+
+/// Ripples spreading out from each key as it's pressed
+///
+/// Both sides see the presses of both halves, so a ripple started on one side
+/// carries on across the other.
+pub struct Typing {
+    seed: u8,
+    tick: U16F16,
+    colour: Option<ColorRGB>,
+    ripples: heapless::Deque<Ripple, 8>,
+}
+
+const TICK_RATE: U16F16 = fixed!(0.5: U16F16);
+
+/// How long a ripple lasts, in ticks
+const LIFETIME: I16F16 = fixed!(60.0: I16F16);
+
+/// How far a ripple travels each tick (mm)
+const SPEED: I16F16 = fixed!(4.0: I16F16);
+
+const RING_WIDTH: I16F16 = fixed!(25.0: I16F16);
+
+impl Default for Typing {
+    fn default() -> Self {
+        let colour = if MyRng.gen_bool(0.3) {
+            None
+        } else {
+            Some(rand_rainbow())
+        };
+
+        Self::new_from_sync((colour, MyRng.gen()))
+    }
+}
+
+fn tick_delta(a: U16F16, b: U16F16) -> U16F16 {
+    wrapping_delta_u(a, b, U16F16::ZERO, U16F16::MAX)
+}
+
+impl Typing {
+    fn age(&self, ripple: &Ripple) -> I16F16 {
+        tick_delta(self.tick, ripple.instant).saturating_to_num::<I16F16>()
+    }
+
+    /// The colour of a ripple from a press, the same on both sides as they
+    /// share the seed and tick in step
+    fn ripple_colour(&self, row: u8, col: u8) -> ColorRGB {
+        let press = u64::from(self.seed)
+            | u64::from(row) << 8
+            | u64::from(col) << 16
+            | u64::from(self.tick.to_num::<u16>()) << 24;
+
+        seeded_rainbow(&mut SmallRng::seed_from_u64(splitmix64(press)))
+    }
+}
+
+impl Animation for Typing {
+    /// The colour, if there's just the one, and the seed so that both sides
+    /// colour ripples the same
+    type SyncMessage = (Option<ColorRGB>, u8);
+
+    fn tick_rate(&self) -> Duration {
+        Duration::from_hz(60)
+    }
+
+    fn tick(&mut self) {
+        self.tick = self.tick.wrapping_add(TICK_RATE);
+
+        while self
+            .ripples
+            .back()
+            .map_or(false, |r| self.age(r) > LIFETIME)
+        {
+            let _ = self.ripples.pop_back();
+        }
+    }
+
+    fn key_event(&mut self, event: Event) {
+        let Event::Press(row, col) = event else {
+            return;
+        };
+
+        let Some((x, y)) = layout::switch_location(row, col) else {
+            return;
+        };
+
+        if self.ripples.is_full() {
+            let _ = self.ripples.pop_back();
+        }
+
+        let _ = self.ripples.push_front(Ripple {
+            x: I16F16::from_num(x),
+            y: I16F16::from_num(y),
+            instant: self.tick,
+            colour: self.colour.unwrap_or_else(|| self.ripple_colour(row, col)),
+        });
+    }
+
+    fn render(&self, light: &Light) -> ColorRGB {
+        let xx = I16F16::from_num(light.location.0);
+        let yy = I16F16::from_num(light.location.1);
+
+        let mut out = ColorRGB::Black;
+
+        for ripple in self.ripples.iter() {
+            let dx = ripple.x.dist(xx);
+            let dy = ripple.y.dist(yy);
+
+            let dist = dx.saturating_mul(dx).saturating_add(dy.saturating_mul(dy));
+            let dist = I16F16::saturating_from_num(sqrt(dist.to_num::<f32>()));
+
+            let age = self.age(ripple);
+            let radius = age * SPEED;
+
+            let b = I16F16::ONE
+                .saturating_sub(radius.dist(dist) / RING_WIDTH)
+                .clamp(I16F16::ZERO, I16F16::ONE);
+
+            // fade out as the ripple spreads
+            let remaining = I16F16::ONE
+                .saturating_sub(age / LIFETIME)
+                .clamp(I16F16::ZERO, I16F16::ONE);
+
+            let b = b.saturating_mul(remaining);
+
+            let level = ease_fade(
+                b.saturating_to_num::<U0F16>()
+                    .clamp(U0F16::ZERO, U0F16::MAX),
+            );
+
+            let mut colour = ripple.colour;
+            colour.scale(level);
+
+            out.r = out.r.saturating_add(colour.r);
+            out.g = out.g.saturating_add(colour.g);
+            out.b = out.b.saturating_add(colour.b);
+        }
+
+        out
+    }
+
+    fn construct_sync(&self) -> Self::SyncMessage {
+        (self.colour, self.seed)
+    }
+
+    fn sync(&mut self, (colour, seed): Self::SyncMessage) {
+        self.colour = colour;
+        self.seed = seed;
+    }
+
+    fn new_from_sync((colour, seed): Self::SyncMessage) -> Self {
+        Self {
+            seed,
+            tick: Default::default(),
+            colour,
+            ripples: Default::default(),
+        }
+    }
+}
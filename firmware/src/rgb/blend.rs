@@ -0,0 +1,75 @@
+//! Mixing animation frames together and correcting them on their way to the
+//! leds
+//!
+//! Kept apart from the runner so that the preview tool renders the same way.
+
+use core::array;
+
+use cichlid::ColorRGB;
+use embassy_time::Duration;
+use fixed::types::{U16F16, U32F32};
+
+use super::math_utils::{ease_fade, powf};
+
+/// The switch leds are rather blue compared to the underglow
+pub const COLOUR_CORRECTION: ColorRGB = ColorRGB::new(190, 200, 255);
+
+/// The gamma exponent used unless the host picks another, in tenths
+pub const DEFAULT_GAMMA: u8 = 19;
+
+/// How long to crossfade from one animation to the next
+pub const FADE_DURATION: Duration = Duration::from_secs(3);
+
+/// How far through a fade lasting `over` we are after `duration`, eased
+pub fn ease_fade_on_time(duration: Duration, over: Duration) -> u8 {
+    if duration > over {
+        255
+    } else {
+        let n = U32F32::saturating_from_num(duration.as_ticks() as u32);
+        let d = U32F32::saturating_from_num(over.as_ticks() as u32);
+        ease_fade((n / d).saturating_to_num())
+    }
+}
+
+/// Applies gamma correction, carrying the rounding error over to the next
+/// frame so that dim colours dither rather than band
+#[derive(Default, Clone, Copy)]
+pub struct GammaErrorTracker {
+    r: U16F16,
+    g: U16F16,
+    b: U16F16,
+}
+
+impl GammaErrorTracker {
+    pub fn process(&mut self, color: ColorRGB, gamma: &Gamma) -> ColorRGB {
+        let Gamma(gamma) = gamma;
+
+        let r = gamma[color.r as usize] + self.r;
+        self.r = r.frac();
+        let r = r.int().saturating_to_num();
+
+        let g = gamma[color.g as usize] + self.g;
+        self.g = g.frac();
+        let g = g.int().saturating_to_num();
+
+        let b = gamma[color.b as usize] + self.b;
+        self.b = b.frac();
+        let b = b.int().saturating_to_num();
+
+        ColorRGB { r, g, b }
+    }
+}
+
+/// Levels to send to the leds for each linear level
+pub struct Gamma([U16F16; 256]);
+
+impl Gamma {
+    /// `tenths` of the exponent, so 19 is 1.9
+    pub fn new(tenths: u8) -> Self {
+        let exponent = tenths as f32 / 10.0;
+
+        Self(array::from_fn(|n| {
+            U16F16::saturating_from_num(255.0 * powf(n as f32 / 255.0, exponent))
+        }))
+    }
+}
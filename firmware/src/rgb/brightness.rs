@@ -0,0 +1,104 @@
+//! The overall brightness of the leds
+//!
+//! Changed by key actions or the host, the primary side passes changes on to
+//! the other side. Both sides remember the brightness across reboots.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    flash, interboard,
+    messages::{device_to_device::DeviceToDevice, reliable_msg},
+    side,
+    utils::log,
+};
+
+const STEP: u8 = 25;
+const MIN_LEVEL: u8 = 15;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub struct Brightness {
+    pub level: u8,
+    pub on: bool,
+}
+
+impl Brightness {
+    const DEFAULT: Self = Self {
+        level: 180,
+        on: true,
+    };
+
+    /// The level the leds should be shown at
+    pub fn effective(self) -> u8 {
+        if self.on {
+            self.level
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Adjust {
+    Up,
+    Down,
+    Toggle,
+}
+
+static CURRENT: Mutex<ThreadModeRawMutex, Cell<Brightness>> =
+    Mutex::new(Cell::new(Brightness::DEFAULT));
+
+pub async fn init() {
+    if let Some(b) = flash::get::<Brightness>().await {
+        log::info!("Loaded brightness: {:?}", b);
+        CURRENT.lock(|c| c.set(b));
+    }
+}
+
+pub fn current() -> Brightness {
+    CURRENT.lock(|c| c.get())
+}
+
+fn apply(brightness: Brightness) {
+    CURRENT.lock(|c| c.set(brightness));
+
+    flash::save_later(&brightness);
+}
+
+/// Change the brightness, passing it on to the other side if we're primary
+pub async fn set(brightness: Brightness) {
+    apply(brightness);
+
+    if side::is_primary() {
+        let msg = DeviceToDevice::SyncBrightness(brightness);
+        interboard::send_msg(reliable_msg(msg), 3).await;
+    }
+}
+
+pub async fn adjust(adjust: Adjust) {
+    let mut b = current();
+
+    match adjust {
+        Adjust::Up => {
+            b.level = b.level.saturating_add(STEP);
+            b.on = true;
+        }
+        Adjust::Down => {
+            b.level = b.level.saturating_sub(STEP).max(MIN_LEVEL);
+            b.on = true;
+        }
+        Adjust::Toggle => b.on = !b.on,
+    }
+
+    set(b).await;
+}
+
+/// The brightness as decided by the primary side
+pub fn synced(brightness: Brightness) {
+    if !side::is_primary() {
+        apply(brightness);
+    }
+}
@@ -0,0 +1,163 @@
+//! Correcting for differences between leds, set from the host
+//!
+//! Each kind of light has a correction that scales its channels, which
+//! particular lights can override, and the gamma curve can be changed. Each
+//! side keeps its own copy as they have their own leds.
+
+use core::cell::{Cell, RefCell};
+
+use cichlid::ColorRGB;
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use portable_atomic::{AtomicU32, Ordering};
+use serde::{Deserialize, Serialize};
+use shared::host_to_device::{CalibrationEdit, Colour, LightKind, TestPattern};
+
+use crate::{flash, utils::log};
+
+use super::{
+    blend::{Gamma, COLOUR_CORRECTION, DEFAULT_GAMMA},
+    layout::{self, Light, NUM_LEDS},
+};
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Calibration {
+    switch: Colour,
+    underglow: Colour,
+    /// Lights with their own correction, by index
+    lights: heapless::Vec<(u8, Colour), { NUM_LEDS as usize }>,
+    /// Gamma exponent, in tenths
+    gamma: u8,
+}
+
+impl Calibration {
+    const DEFAULT: Self = Self {
+        switch: Colour {
+            r: COLOUR_CORRECTION.r,
+            g: COLOUR_CORRECTION.g,
+            b: COLOUR_CORRECTION.b,
+        },
+        underglow: Colour {
+            r: 255,
+            g: 255,
+            b: 255,
+        },
+        lights: heapless::Vec::new(),
+        gamma: DEFAULT_GAMMA,
+    };
+
+    fn correction(&self, index: u8, light: &Light) -> Colour {
+        let own = self.lights.iter().find(|(i, _)| *i == index);
+
+        match (own, light.kind) {
+            (Some((_, c)), _) => *c,
+            (None, layout::Kind::Switch) => self.switch,
+            (None, layout::Kind::Underglow) => self.underglow,
+        }
+    }
+}
+
+static CURRENT: Mutex<ThreadModeRawMutex, RefCell<Calibration>> =
+    Mutex::new(RefCell::new(Calibration::DEFAULT));
+
+/// Bumped on every change, so the runner knows to rebuild its [`Corrections`]
+static VERSION: AtomicU32 = AtomicU32::new(0);
+
+static PATTERN: Mutex<ThreadModeRawMutex, Cell<Option<TestPattern>>> = Mutex::new(Cell::new(None));
+
+pub async fn init() {
+    if let Some(c) = flash::get::<Calibration>().await {
+        log::info!("Loaded led calibration, gamma {}", c.gamma);
+        CURRENT.lock(|cur| *cur.borrow_mut() = c);
+    }
+}
+
+pub fn edit(edit: CalibrationEdit) {
+    if let CalibrationEdit::ShowPattern(pattern) = edit {
+        PATTERN.lock(|p| p.set(pattern));
+        return;
+    }
+
+    CURRENT.lock(|c| {
+        let mut c = c.borrow_mut();
+
+        match edit {
+            CalibrationEdit::Reset => *c = Calibration::DEFAULT,
+            CalibrationEdit::SetKind(LightKind::Switch, colour) => c.switch = colour,
+            CalibrationEdit::SetKind(LightKind::Underglow, colour) => c.underglow = colour,
+            CalibrationEdit::SetLight(index, _) if index as u16 >= NUM_LEDS => {
+                log::warn!("No light at index {}", index);
+            }
+            CalibrationEdit::SetLight(index, colour) => {
+                c.lights.retain(|(i, _)| *i != index);
+
+                if let Some(colour) = colour {
+                    // there's room for every light
+                    let _ = c.lights.push((index, colour));
+                }
+            }
+            CalibrationEdit::SetGamma(0) => log::warn!("A gamma of zero isn't much use"),
+            CalibrationEdit::SetGamma(tenths) => c.gamma = tenths,
+            CalibrationEdit::ShowPattern(_) => {}
+        }
+
+        flash::save_later(&*c);
+    });
+
+    VERSION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// The test pattern being shown instead of the animation, if there is one
+pub fn pattern_frame() -> Option<[ColorRGB; NUM_LEDS as usize]> {
+    let pattern = PATTERN.lock(|p| p.get())?;
+
+    Some(core::array::from_fn(|i| match pattern {
+        TestPattern::Solid(c) => ColorRGB::new(c.r, c.g, c.b),
+        TestPattern::Ramp => {
+            let level = (i * 255 / (NUM_LEDS as usize - 1)) as u8;
+            ColorRGB::new(level, level, level)
+        }
+    }))
+}
+
+/// The calibration in the form the runner wants it
+pub struct Corrections {
+    lights: [ColorRGB; NUM_LEDS as usize],
+    pub gamma: Gamma,
+    version: u32,
+}
+
+impl Corrections {
+    pub fn new(lights: &[Light; NUM_LEDS as usize]) -> Self {
+        let version = VERSION.load(Ordering::Relaxed);
+
+        CURRENT.lock(|c| {
+            let c = c.borrow();
+
+            Self {
+                lights: core::array::from_fn(|i| {
+                    let Colour { r, g, b } = c.correction(i as u8, &lights[i]);
+                    ColorRGB::new(r, g, b)
+                }),
+                gamma: Gamma::new(c.gamma),
+                version,
+            }
+        })
+    }
+
+    /// Pick up any changes to the calibration
+    pub fn refresh(&mut self, lights: &[Light; NUM_LEDS as usize]) {
+        if self.version != VERSION.load(Ordering::Relaxed) {
+            *self = Self::new(lights);
+        }
+    }
+
+    pub fn apply(&self, index: usize, mut colour: ColorRGB) -> ColorRGB {
+        let correction = self.lights[index];
+
+        if correction != ColorRGB::White {
+            colour.scale_from_other(correction);
+        }
+
+        colour
+    }
+}
@@ -0,0 +1,47 @@
+//! Led colours streamed from the host, shown in place of the animation
+//!
+//! The animation keeps running underneath so it can carry on where it left
+//! off once the host stops sending frames.
+
+use core::cell::RefCell;
+
+use cichlid::ColorRGB;
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use embassy_time::{Duration, Instant};
+use portable_atomic::{AtomicU64, Ordering};
+use shared::host_to_device::Colour;
+
+use super::layout::NUM_LEDS;
+
+/// How long after the last frame before going back to the animation
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+static FRAME: Mutex<ThreadModeRawMutex, RefCell<[ColorRGB; NUM_LEDS as usize]>> =
+    Mutex::new(RefCell::new([ColorRGB::Black; NUM_LEDS as usize]));
+
+/// When a frame last arrived, zero if one never has
+static LAST_FRAME: AtomicU64 = AtomicU64::new(0);
+
+pub fn write(offset: u8, colours: &[Colour]) {
+    FRAME.lock(|f| {
+        let mut f = f.borrow_mut();
+
+        for (dest, c) in f.iter_mut().skip(offset as usize).zip(colours) {
+            *dest = ColorRGB::new(c.r, c.g, c.b);
+        }
+    });
+
+    LAST_FRAME.store(Instant::now().as_ticks().max(1), Ordering::Relaxed);
+}
+
+/// Whether the host is currently driving the leds
+pub fn is_active() -> bool {
+    match LAST_FRAME.load(Ordering::Relaxed) {
+        0 => false,
+        t => Instant::from_ticks(t).elapsed() < TIMEOUT,
+    }
+}
+
+pub fn frame() -> [ColorRGB; NUM_LEDS as usize] {
+    FRAME.lock(|f| *f.borrow())
+}
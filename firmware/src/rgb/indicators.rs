@@ -0,0 +1,107 @@
+//! Colour hints for the active layer and held modifiers, drawn over whatever
+//! animation is running
+//!
+//! Keys that do something on the active layer are tinted with that layer's
+//! colour, keys bound to a held modifier light up in the modifier's colour, and
+//! the underglow shows caps lock.
+
+use cichlid::ColorRGB;
+use keyberon::{action::Action, key_code::KeyCode};
+
+use crate::{
+    keys::{self, layout::LAYERS, CustomEvent},
+    messages::device_to_device::Modifiers,
+};
+
+use super::layout::{Kind, Light};
+
+/// Indexed by layer, the base layer isn't tinted
+const LAYER_COLOURS: [Option<ColorRGB>; 4] = [
+    None,
+    Some(ColorRGB::new(40, 90, 255)),
+    Some(ColorRGB::new(255, 120, 0)),
+    Some(ColorRGB::new(200, 0, 200)),
+];
+const LAYER_STRENGTH: u8 = 200;
+
+const SHIFT_COLOUR: ColorRGB = ColorRGB::new(0, 255, 60);
+const CTRL_COLOUR: ColorRGB = ColorRGB::new(255, 0, 40);
+const ALT_COLOUR: ColorRGB = ColorRGB::new(255, 220, 0);
+const GUI_COLOUR: ColorRGB = ColorRGB::new(180, 0, 255);
+
+const CAPS_LOCK_COLOUR: ColorRGB = ColorRGB::new(255, 255, 255);
+const CAPS_LOCK_STRENGTH: u8 = 160;
+
+/// What the indicators are currently showing
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct State {
+    layer: u8,
+    modifiers: Modifiers,
+}
+
+impl State {
+    pub fn current() -> Self {
+        Self {
+            layer: keys::current_layer(),
+            modifiers: keys::current_modifiers(),
+        }
+    }
+
+    fn action_at(&self, col: u8, row: u8) -> Option<&'static Action<CustomEvent>> {
+        let action = LAYERS
+            .get(self.layer as usize)?
+            .get(row as usize)?
+            .get(col as usize)?;
+
+        match action {
+            Action::Trans => LAYERS[0].get(row as usize)?.get(col as usize),
+            action => Some(action),
+        }
+    }
+
+    fn modifier_colour(&self, action: &Action<CustomEvent>) -> Option<ColorRGB> {
+        let k = match action {
+            Action::KeyCode(k) => *k,
+            Action::HoldTap(ht) => match ht.hold {
+                Action::KeyCode(k) => k,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let m = self.modifiers;
+
+        match k {
+            KeyCode::LShift | KeyCode::RShift if m.shift() => Some(SHIFT_COLOUR),
+            KeyCode::LCtrl | KeyCode::RCtrl if m.ctrl() => Some(CTRL_COLOUR),
+            KeyCode::LAlt | KeyCode::RAlt if m.alt() => Some(ALT_COLOUR),
+            KeyCode::LGui | KeyCode::RGui if m.gui() => Some(GUI_COLOUR),
+            _ => None,
+        }
+    }
+
+    /// The colour to draw over a light, and how strongly
+    pub fn overlay(&self, light: &Light) -> Option<(ColorRGB, u8)> {
+        match (light.kind, light.position) {
+            (Kind::Underglow, _) => self
+                .modifiers
+                .caps_lock()
+                .then_some((CAPS_LOCK_COLOUR, CAPS_LOCK_STRENGTH)),
+            (Kind::Switch, Some((col, row))) => {
+                let action = self.action_at(col, row)?;
+
+                if let Some(colour) = self.modifier_colour(action) {
+                    return Some((colour, 255));
+                }
+
+                if matches!(action, Action::NoOp | Action::Trans) {
+                    return None;
+                }
+
+                let colour = (*LAYER_COLOURS.get(self.layer as usize)?)?;
+                Some((colour, LAYER_STRENGTH))
+            }
+            (Kind::Switch, None) => None,
+        }
+    }
+}
@@ -201,3 +201,11 @@ pub mod right {
 }
 
 pub use right::RIGHT;
+
+/// Location of the switch at a matrix position, from either side
+pub fn switch_location(row: u8, col: u8) -> Option<(i16, i16)> {
+    LEFT.iter()
+        .chain(RIGHT.iter())
+        .find(|l| l.position == Some((col, row)))
+        .map(|l| l.location)
+}
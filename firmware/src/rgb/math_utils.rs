@@ -36,6 +36,34 @@ pub(crate) fn sqr(x: I16F16) -> I16F16 {
     x * x
 }
 
+/// The rp2040 has no fpu, but its rom does have a fast square root
+#[cfg(target_os = "none")]
+pub(crate) fn sqrt(x: f32) -> f32 {
+    embassy_rp::rom_data::float_funcs::fsqrt(x)
+}
+
+#[cfg(not(target_os = "none"))]
+pub(crate) fn sqrt(x: f32) -> f32 {
+    x.sqrt()
+}
+
+/// `x` to the power of `y`, for `x` from 0 to 1
+#[cfg(target_os = "none")]
+pub(crate) fn powf(x: f32, y: f32) -> f32 {
+    use embassy_rp::rom_data::float_funcs::{fexp, fln};
+
+    if x <= 0.0 {
+        0.0
+    } else {
+        fexp(y * fln(x))
+    }
+}
+
+#[cfg(not(target_os = "none"))]
+pub(crate) fn powf(x: f32, y: f32) -> f32 {
+    x.powf(y)
+}
+
 pub(crate) fn rand_decimal() -> I4F12 {
     I4F12::from_bits(MyRng.gen()).frac()
 }
@@ -44,6 +72,11 @@ pub(crate) fn rand_rainbow() -> ColorRGB {
     rainbow(rand_decimal())
 }
 
+/// A random colour from an rng both sides have seeded the same
+pub(crate) fn seeded_rainbow(rng: &mut impl Rng) -> ColorRGB {
+    rainbow(I4F12::from_bits(rng.gen()).frac())
+}
+
 pub(crate) fn rainbow(x: I4F12) -> ColorRGB {
     let x = fixed!(0.5: I4F12) - x;
 
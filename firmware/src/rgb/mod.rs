@@ -6,26 +6,33 @@ use embassy_rp::{
     Peripheral,
 };
 use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, channel::Channel};
-use embassy_time::{Duration, Timer};
+use embassy_time::Instant;
 
 use crate::{
-    interboard::{self, THIS_SIDE_MESSAGE_BUS},
+    interboard::{self, clock::SharedInstant, THIS_SIDE_MESSAGE_BUS},
     messages::{device_to_device::DeviceToDevice, reliable_msg},
     side,
 };
 
-use self::{animation::Animation, animations::DynAnimation};
-
 pub mod animation;
 pub mod animations;
+pub mod blend;
+pub mod brightness;
+pub mod calibration;
+pub mod direct;
 mod driver;
+pub mod indicators;
 pub mod layout;
 pub mod math_utils;
+pub mod playlist;
+pub mod power_budget;
 mod runner;
+pub mod scripts;
+pub mod settings;
 
 pub(super) static RGB_CMD_CHANNEL: Channel<ThreadModeRawMutex, Command, 1> = Channel::new();
 
-pub fn init(
+pub async fn init(
     spawner: &Spawner,
     common: &mut Common<'static, PIO1>,
     sm: StateMachine<'static, PIO1, 0>,
@@ -34,12 +41,16 @@ pub fn init(
 ) {
     let d = driver::Ws2812::new(common, sm, pin, dma);
 
+    settings::init().await;
+    playlist::init().await;
+    scripts::init().await;
+    brightness::init().await;
+    power_budget::init().await;
+    calibration::init().await;
+
     spawner.must_spawn(runner::rgb_runner(d));
     spawner.must_spawn(command_listener());
-
-    if side::this_side_has_usb() {
-        spawner.must_spawn(animation_randomizer());
-    }
+    spawner.must_spawn(animation_rotator());
 }
 
 #[embassy_executor::task]
@@ -48,33 +59,50 @@ async fn command_listener() {
 
     loop {
         let cmd = match sub.next_message_pure().await {
-            DeviceToDevice::SetAnimation(a) => Command::SetNextAnimation(a),
-            DeviceToDevice::SyncAnimation(a) => Command::SyncAnimation(a),
+            DeviceToDevice::SetAnimation(a, epoch) => {
+                Command::SetNextAnimation(a, epoch.to_local())
+            }
+            DeviceToDevice::SyncAnimation(a, epoch) => Command::SyncAnimation(a, epoch.to_local()),
+            DeviceToDevice::SyncBrightness(b) => {
+                brightness::synced(b);
+                continue;
+            }
             _ => continue,
         };
 
+        // the primary side is the one deciding what to show
+        if side::is_primary() {
+            continue;
+        }
+
         send_cmd(cmd).await;
     }
 }
 
 #[embassy_executor::task]
-async fn animation_randomizer() {
-    loop {
-        Timer::after(Duration::from_secs(60 * 5)).await;
+async fn animation_rotator() {
+    side::while_primary(playlist::rotate).await;
+}
 
-        let anim = DynAnimation::random();
-        let sync = anim.construct_sync();
+/// Switch to an animation on both sides, only the primary side should do this
+async fn show(sync: animations::AnimationSync) {
+    let epoch = Instant::now();
 
-        send_cmd(Command::SetNextAnimation(sync.clone())).await;
-        interboard::send_msg(reliable_msg(DeviceToDevice::SetAnimation(sync)), 3).await;
-    }
+    send_cmd(Command::SetNextAnimation(sync.clone(), epoch)).await;
+
+    let cmd = DeviceToDevice::SetAnimation(sync, SharedInstant::from_local(epoch));
+    interboard::send_msg(reliable_msg(cmd), 3).await;
 }
 
 pub async fn send_cmd(cmd: Command) {
     RGB_CMD_CHANNEL.send(cmd).await
 }
 
+/// Animations are given the instant they started at, both sides tick them from
+/// then on so that they stay in step
 pub enum Command {
-    SetNextAnimation(animations::AnimationSync),
-    SyncAnimation(animations::AnimationSync),
+    SetNextAnimation(animations::AnimationSync, Instant),
+    SyncAnimation(animations::AnimationSync, Instant),
+    /// Send the animation we're performing (or transitioning to) to the other side
+    Resync,
 }
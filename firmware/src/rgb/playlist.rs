@@ -0,0 +1,166 @@
+//! Cycling through a list of animations chosen by the host
+//!
+//! Both sides keep a copy of the playlist so that whichever ends up primary
+//! can carry on with it, only the primary side moves through it. Without a
+//! playlist a random animation is picked every few minutes instead, if the
+//! animation settings allow it.
+
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Duration, Timer};
+use rand::seq::SliceRandom;
+use shared::host_to_device::{
+    AnimationChoice, Playlist, PlaylistEdit, PlaylistEntry, MAX_PLAYLIST_LEN,
+};
+
+use crate::{flash, rng::MyRng, side, utils::log};
+
+use super::{
+    animation::Animation,
+    animations::{AnimationSync, DynAnimation},
+    settings, show,
+};
+
+const RANDOM_PERIOD: Duration = Duration::from_secs(60 * 5);
+/// Entries are shown for at least this long, so the fade into each one gets to
+/// finish and the other side isn't flooded with changes
+const MIN_PERIOD: Duration = Duration::from_secs(5);
+
+static PLAYLIST: Mutex<ThreadModeRawMutex, Playlist> = Mutex::new(Playlist::EMPTY);
+
+static CHANGED: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+pub async fn init() {
+    if let Some(p) = flash::get::<Playlist>().await {
+        log::info!("Loaded playlist: {:?}", p);
+        *PLAYLIST.lock().await = p;
+    }
+}
+
+/// The animation the playlist starts on, if there is one
+pub async fn initial_animation() -> Option<AnimationChoice> {
+    let playlist = PLAYLIST.lock().await;
+
+    playlist
+        .pinned_entry()
+        .or_else(|| playlist.entries.first())
+        .map(|e| e.animation)
+}
+
+pub async fn edit(edit: PlaylistEdit) {
+    let pinned = {
+        let mut playlist = PLAYLIST.lock().await;
+
+        match edit {
+            PlaylistEdit::Clear => *playlist = Playlist::EMPTY,
+            PlaylistEdit::Push(entry) => {
+                if playlist.entries.push(entry).is_err() {
+                    log::warn!("Playlist is full");
+                }
+            }
+            PlaylistEdit::Remove(idx) => {
+                if (idx as usize) < playlist.entries.len() {
+                    playlist.entries.remove(idx as usize);
+
+                    // keep the pin on the same entry
+                    playlist.pinned = match playlist.pinned {
+                        Some(p) if p == idx => None,
+                        Some(p) if p > idx => Some(p - 1),
+                        p => p,
+                    };
+                }
+            }
+            PlaylistEdit::SetShuffle(shuffle) => playlist.shuffle = shuffle,
+            PlaylistEdit::Pin(idx) => playlist.pinned = idx,
+        }
+
+        flash::save_later(&*playlist);
+
+        match edit {
+            PlaylistEdit::Pin(Some(_)) => playlist.pinned_entry().copied(),
+            _ => None,
+        }
+    };
+
+    CHANGED.signal(());
+
+    if let Some(entry) = pinned {
+        if side::is_primary() {
+            show(AnimationSync::from(entry.animation)).await;
+        }
+    }
+}
+
+/// The order we're going through the playlist in
+#[derive(Default)]
+struct Order {
+    indices: heapless::Vec<u8, MAX_PLAYLIST_LEN>,
+    position: usize,
+}
+
+impl Order {
+    fn rebuild(&mut self, playlist: &Playlist) {
+        self.indices = (0..playlist.entries.len() as u8).collect();
+        self.position = 0;
+
+        if playlist.shuffle {
+            self.indices.shuffle(&mut MyRng);
+        }
+    }
+
+    fn current<'a>(&mut self, playlist: &'a Playlist) -> Option<&'a PlaylistEntry> {
+        if self.indices.len() != playlist.entries.len() {
+            self.rebuild(playlist);
+        }
+
+        playlist
+            .entries
+            .get(*self.indices.get(self.position)? as usize)
+    }
+
+    fn advance<'a>(&mut self, playlist: &'a Playlist) -> Option<&'a PlaylistEntry> {
+        self.position += 1;
+
+        if self.position >= self.indices.len() {
+            self.rebuild(playlist);
+        }
+
+        self.current(playlist)
+    }
+}
+
+/// Move through the playlist, or pick random animations if there isn't one
+pub async fn rotate() {
+    let mut order = Order::default();
+
+    loop {
+        let playlist = PLAYLIST.lock().await.clone();
+
+        // a pinned entry stays until the playlist changes
+        let period = match (playlist.pinned_entry(), order.current(&playlist)) {
+            (Some(_), _) => None,
+            (None, Some(entry)) => Some(Duration::from_secs(entry.secs as u64).max(MIN_PERIOD)),
+            (None, None) => Some(RANDOM_PERIOD),
+        };
+
+        let timeout = async {
+            match period {
+                Some(period) => Timer::after(period).await,
+                None => core::future::pending().await,
+            }
+        };
+
+        if let Either::Second(()) = select(timeout, CHANGED.wait()).await {
+            order = Order::default();
+            continue;
+        }
+
+        let sync = match order.advance(&playlist) {
+            Some(entry) => AnimationSync::from(entry.animation),
+            None if settings::randomise().await => DynAnimation::random().construct_sync(),
+            None => continue,
+        };
+
+        show(sync).await;
+    }
+}
@@ -0,0 +1,50 @@
+//! How much current each side lets its leds draw
+//!
+//! Set by the host, each side keeps its own copy as the budget depends on
+//! whether that side is the one plugged into usb.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{raw::ThreadModeRawMutex, Mutex};
+use shared::host_to_device::PowerBudget;
+
+use crate::{flash, side, utils::log};
+
+use super::{layout::NUM_LEDS, runner::QUIESCENT_CURRENT};
+
+/// The leds draw this much even when they're off, so a smaller budget can't be
+/// kept to (mA)
+const MIN_BUDGET: u16 = NUM_LEDS * QUIESCENT_CURRENT as u16;
+
+static CURRENT: Mutex<ThreadModeRawMutex, Cell<PowerBudget>> =
+    Mutex::new(Cell::new(PowerBudget::DEFAULT));
+
+pub async fn init() {
+    if let Some(b) = flash::get::<PowerBudget>().await {
+        log::info!("Loaded power budget: {:?}", b);
+        CURRENT.lock(|c| c.set(b));
+    }
+}
+
+pub fn set(mut budget: PowerBudget) {
+    if budget.with_usb < MIN_BUDGET || budget.without_usb < MIN_BUDGET {
+        log::warn!("Power budget raised to the minimum of {}mA", MIN_BUDGET);
+        budget.with_usb = budget.with_usb.max(MIN_BUDGET);
+        budget.without_usb = budget.without_usb.max(MIN_BUDGET);
+    }
+
+    CURRENT.lock(|c| c.set(budget));
+
+    flash::save_later(&budget);
+}
+
+/// The most this side's leds should draw (mA)
+pub fn for_this_side() -> u32 {
+    let budget = CURRENT.lock(|c| c.get());
+
+    if side::this_side_has_usb() {
+        budget.with_usb as u32
+    } else {
+        budget.without_usb as u32
+    }
+}
@@ -1,45 +1,181 @@
 use core::array;
 
 use cichlid::ColorRGB;
-use embassy_futures::select::{select, select3};
+use embassy_futures::{
+    select::{select, select3},
+    yield_now,
+};
 use embassy_rp::peripherals::PIO1;
 use embassy_time::{Duration, Instant, Timer};
-use fixed::types::{U16F16, U32F32};
-use fixed_macro::fixed;
 
 use crate::{
-    interboard,
+    idle,
+    interboard::{self, clock::SharedInstant},
+    keys::KEY_EVENTS,
     messages::{device_to_device::DeviceToDevice, reliable_msg, unreliable_msg},
+    power,
     side::get_side,
-    utils::Ticker,
+    utils::log,
 };
 
 use super::{
     animation::Animation,
     animations,
+    blend::{ease_fade_on_time, GammaErrorTracker, FADE_DURATION},
+    brightness,
+    calibration::{self, Corrections},
+    direct,
     driver::Ws2812,
+    indicators,
     layout::{self, Light, NUM_LEDS},
-    math_utils::ease_fade,
-    RGB_CMD_CHANNEL,
+    power_budget, settings, RGB_CMD_CHANNEL,
 };
 
-const MAX_LEVEL: u8 = 180;
-const COLOUR_CORRECTION: ColorRGB = ColorRGB::new(190, 200, 255);
-const FADE_DURATION: Duration = Duration::from_secs(3);
+const BRIGHTNESS_FADE_DURATION: Duration = Duration::from_millis(500);
 
-fn ease_fade_on_time(duration: Duration) -> u8 {
-    if duration > FADE_DURATION {
-        255
-    } else {
-        let n = U32F32::saturating_from_num(duration.as_ticks() as u32);
-        let d = U32F32::saturating_from_num(FADE_DURATION.as_ticks() as u32);
-        ease_fade((n / d).saturating_to_num())
+/// Current drawn by an led for each colour channel when fully on (mA)
+const CHANNEL_CURRENT: u32 = 12;
+/// Current drawn by an led even when it's off (mA)
+pub(super) const QUIESCENT_CURRENT: u32 = 1;
+
+/// Roughly how much current the leds will draw showing `colours` (mA)
+fn estimate_current(colours: &[ColorRGB]) -> u32 {
+    let levels: u32 = colours
+        .iter()
+        .map(|c| c.r as u32 + c.g as u32 + c.b as u32)
+        .sum();
+
+    colours.len() as u32 * QUIESCENT_CURRENT + levels * CHANNEL_CURRENT / 255
+}
+
+/// Dim the whole frame if it would draw more than this side's budget
+fn limit_current(colours: &mut [ColorRGB]) {
+    let estimate = estimate_current(colours);
+    let budget = power_budget::for_this_side();
+
+    if estimate <= budget {
+        return;
+    }
+
+    // the leds draw their quiescent current no matter what
+    let quiescent = colours.len() as u32 * QUIESCENT_CURRENT;
+    let available = budget.saturating_sub(quiescent);
+    let wanted = estimate - quiescent;
+
+    // already black, the budget is too small for the leds to be on at all
+    if wanted == 0 {
+        return;
+    }
+
+    // these are already gamma corrected, so current scales with them directly
+    let scale = (available * 255 / wanted) as u8;
+    for c in colours {
+        c.scale(scale);
+    }
+}
+
+/// Eases between brightness levels when the brightness changes, or when the
+/// keyboard goes idle or wakes up
+struct BrightnessFade {
+    from: u8,
+    to: u8,
+    started: Instant,
+}
+
+impl BrightnessFade {
+    fn target() -> u8 {
+        if idle::is_idle() {
+            0
+        } else {
+            brightness::current().effective()
+        }
+    }
+
+    fn new() -> Self {
+        let level = Self::target();
+
+        Self {
+            from: level,
+            to: level,
+            started: Instant::now(),
+        }
+    }
+
+    fn level_now(&self) -> u8 {
+        let t = ease_fade_on_time(self.started.elapsed(), BRIGHTNESS_FADE_DURATION) as i32;
+        let (from, to) = (self.from as i32, self.to as i32);
+
+        (from + (to - from) * t / 255) as u8
+    }
+
+    fn level(&mut self) -> u8 {
+        let target = Self::target();
+
+        if target != self.to {
+            self.from = self.level_now();
+            self.to = target;
+            self.started = Instant::now();
+        }
+
+        self.level_now()
     }
 }
 
+/// A frame to show instead of the animation: a calibration test pattern, or
+/// one streamed from the host
+fn replacement_frame() -> Option<[ColorRGB; NUM_LEDS as usize]> {
+    calibration::pattern_frame().or_else(|| direct::is_active().then(direct::frame))
+}
+
+/// Commands that turned up while we were paused, only the latest of each kind
+/// matters
+#[derive(Default)]
+struct Pending {
+    animation: Option<(animations::AnimationSync, Instant)>,
+    sync: Option<(animations::AnimationSync, Instant)>,
+    resync: bool,
+}
+
+impl Pending {
+    fn add(&mut self, cmd: super::Command) {
+        match cmd {
+            super::Command::SetNextAnimation(a, epoch) => {
+                // a sync would be for the animation being replaced
+                self.sync = None;
+                self.animation = Some((a, epoch));
+            }
+            super::Command::SyncAnimation(sync, epoch) => self.sync = Some((sync, epoch)),
+            super::Command::Resync => self.resync = true,
+        }
+    }
+
+    fn take(&mut self) -> Option<super::Command> {
+        if let Some((a, epoch)) = self.animation.take() {
+            Some(super::Command::SetNextAnimation(a, epoch))
+        } else if let Some((sync, epoch)) = self.sync.take() {
+            Some(super::Command::SyncAnimation(sync, epoch))
+        } else {
+            core::mem::take(&mut self.resync).then_some(super::Command::Resync)
+        }
+    }
+}
+
+/// Ticks to catch up on at once before letting everything else have a go
+const CATCH_UP_BATCH: u64 = 64;
+
+/// An animation further behind than this skips ahead rather than working
+/// through every tick it missed, so won't match the other side until it's
+/// restarted by a resync or the next one is set
+const MAX_CATCH_UP: Duration = Duration::from_secs(60 * 60);
+
+/// An animation being ticked from when it started, rather than from when it
+/// was last ticked, so that both sides stay in step
 struct PerformingAnimation<'a, T> {
     animation: T,
-    ticker: Ticker,
+    /// When the first tick was due, the rest follow on at the tick rate
+    epoch: Instant,
+    /// Ticks performed since the epoch
+    ticks: u64,
     colours: &'a mut [ColorRGB; NUM_LEDS as usize],
     lights: &'static [Light; NUM_LEDS as usize],
 }
@@ -47,43 +183,106 @@ struct PerformingAnimation<'a, T> {
 impl<'a, T: Animation> PerformingAnimation<'a, T> {
     fn new(
         animation: T,
+        epoch: Instant,
         colours: &'a mut [ColorRGB; NUM_LEDS as usize],
         lights: &'static [Light; NUM_LEDS as usize],
     ) -> Self {
-        let ticker = Ticker::every(animation.tick_rate());
-
         let mut performing_animation = Self {
             animation,
-            ticker,
+            epoch,
+            ticks: 1,
             colours,
             lights,
         };
-        performing_animation.render();
+        performing_animation.animation.tick();
+        performing_animation.draw(indicators::State::current());
         performing_animation
     }
 
     fn reconstruct_from(&mut self, other: PerformingAnimation<T>) {
         self.animation = other.animation;
-        self.ticker = other.ticker;
+        self.epoch = other.epoch;
+        self.ticks = other.ticks;
 
-        self.render();
+        self.draw(indicators::State::current());
     }
 
-    async fn step(&mut self) {
-        self.ticker.next().await;
+    fn sync(&mut self, sync: T::SyncMessage, epoch: Instant) {
+        // a sync for some other animation, such as the one being faded in
+        if core::mem::discriminant(&self.animation.construct_sync())
+            != core::mem::discriminant(&sync)
+        {
+            return;
+        }
+
+        if epoch > self.epoch + Duration::from_ticks(self.period()) {
+            // restarted on the other side, our ticks would be due far in the
+            // future so start again from the sync
+            self.animation = T::new_from_sync(sync);
+            self.ticks = 0;
+        } else {
+            // any ticks missed against an earlier epoch are caught up on by
+            // the next step
+            self.animation.sync(sync);
+        }
+
+        self.epoch = epoch;
+    }
 
-        self.render();
+    fn key_event(&mut self, event: keyberon::layout::Event) {
+        self.animation.key_event(event);
     }
 
-    fn render(&mut self) {
-        self.animation.tick();
+    fn period(&self) -> u64 {
+        self.animation.tick_rate().as_ticks().max(1)
+    }
+
+    /// How many ticks should have been performed by now
+    fn due(&self) -> u64 {
+        Instant::now()
+            .saturating_duration_since(self.epoch)
+            .as_ticks()
+            / self.period()
+            + 1
+    }
+
+    /// Wait for the next tick, then perform it along with any that were missed
+    async fn step(&mut self) {
+        let next = self.epoch + Duration::from_ticks(self.ticks.saturating_mul(self.period()));
+        Timer::at(next).await;
+
+        let due = self.due();
+        if due.saturating_sub(self.ticks) > MAX_CATCH_UP.as_ticks() / self.period() {
+            log::warn!(
+                "Animation fell {} ticks behind, skipping ahead",
+                due - self.ticks
+            );
+            self.ticks = due - 1;
+        }
+
+        // this is cancelled by every output, so keep count as we go
+        while self.ticks < due {
+            let batch = (due - self.ticks).min(CATCH_UP_BATCH);
+            for _ in 0..batch {
+                self.animation.tick();
+            }
+            self.ticks += batch;
 
+            if self.ticks < due {
+                yield_now().await;
+            }
+        }
+
+        self.draw(indicators::State::current());
+    }
+
+    /// Draw the current frame of the animation, with the indicators on top
+    fn draw(&mut self, indicators: indicators::State) {
         for (dest, light) in self.colours.iter_mut().zip(self.lights) {
             let mut color = self.animation.render(light);
-            color.scale(MAX_LEVEL);
 
-            if light.kind == layout::Kind::Switch {
-                color.scale_from_other(COLOUR_CORRECTION);
+            if let Some((overlay, strength)) = indicators.overlay(light) {
+                color.blend(overlay, strength);
             }
 
             *dest = color;
@@ -104,20 +303,25 @@ pub async fn rgb_runner(mut driver: Ws2812<'static, PIO1, 0, { NUM_LEDS as usize
 
     let mut current = PerformingAnimation::new(
         animations::DynAnimation::Null(animations::null::Null),
+        Instant::now(),
         &mut current_colours,
         lights,
     );
 
     let mut next: Option<(Instant, PerformingAnimation<'_, animations::DynAnimation>)> =
-        if crate::side::this_side_has_usb() || cfg!(feature = "probe") {
+        if crate::side::is_primary() || cfg!(feature = "probe") {
             let animation = PerformingAnimation::new(
-                animations::DynAnimation::random(),
+                settings::initial_animation().await,
+                Instant::now(),
                 &mut next_colours,
                 lights,
             );
 
             // reporo the animation to the other side
-            let cmd = DeviceToDevice::SetAnimation(animation.animation.construct_sync());
+            let cmd = DeviceToDevice::SetAnimation(
+                animation.animation.construct_sync(),
+                SharedInstant::from_local(animation.epoch),
+            );
             interboard::send_msg(reliable_msg(cmd), 3).await;
 
             Some((Instant::now(), animation))
@@ -125,37 +329,109 @@ pub async fn rgb_runner(mut driver: Ws2812<'static, PIO1, 0, { NUM_LEDS as usize
             None
         };
 
+    // drained often, as publishing key events waits on every subscriber
+    let mut key_sub = KEY_EVENTS.subscriber().unwrap();
+
+    let mut brightness = BrightnessFade::new();
+
+    // slow animations would otherwise leave the indicators out of date until
+    // their next tick
+    let mut drawn_indicators = indicators::State::current();
+
     let mut last_sync = Instant::now();
     const SYNC_PERIOD: Duration = Duration::from_secs(10);
 
+    let mut pending = Pending::default();
+
+    let mut corrections = Corrections::new(lights);
+
     loop {
+        if power::is_suspended() {
+            driver.write(&[ColorRGB::Black; NUM_LEDS as usize]).await;
+
+            // key events and commands still need taking, or whoever sends
+            // them would back up until we resume
+            loop {
+                match select3(
+                    power::wait_for_resume(),
+                    key_sub.next_message_pure(),
+                    RGB_CMD_CHANNEL.receive(),
+                )
+                .await
+                {
+                    embassy_futures::select::Either3::First(()) => break,
+                    embassy_futures::select::Either3::Second(_) => {}
+                    embassy_futures::select::Either3::Third(cmd) => pending.add(cmd),
+                }
+            }
+
+            // rather than working through everything missed while suspended,
+            // start the animation afresh on both sides
+            if crate::side::is_primary() && pending.animation.is_none() {
+                let sync = current.animation.construct_sync();
+                let epoch = Instant::now();
+
+                let cmd =
+                    DeviceToDevice::SetAnimation(sync.clone(), SharedInstant::from_local(epoch));
+                interboard::send_msg(reliable_msg(cmd), 3).await;
+
+                pending.add(super::Command::SetNextAnimation(sync, epoch));
+            }
+        }
+
         let mut errors = [GammaErrorTracker::default(); NUM_LEDS as usize];
 
+        corrections.refresh(lights);
+
         if let Some((_, next)) = next.take_if(|(f, _)| f.elapsed() > FADE_DURATION) {
             current.reconstruct_from(next);
         }
 
-        if crate::side::this_side_has_usb() && last_sync.elapsed() > SYNC_PERIOD {
+        if crate::side::is_primary() && last_sync.elapsed() > SYNC_PERIOD {
             last_sync = Instant::now();
 
-            let cmd = DeviceToDevice::SyncAnimation(current.animation.construct_sync());
+            let cmd = DeviceToDevice::SyncAnimation(
+                current.animation.construct_sync(),
+                SharedInstant::from_local(current.epoch),
+            );
             interboard::send_msg(unreliable_msg(cmd), 3).await;
         }
 
-        if let Ok(cmd) = RGB_CMD_CHANNEL.try_receive() {
+        if let Some(cmd) = pending
+            .take()
+            .or_else(|| RGB_CMD_CHANNEL.try_receive().ok())
+        {
             match cmd {
-                super::Command::SetNextAnimation(a) => {
+                super::Command::SetNextAnimation(a, epoch) => {
                     next = Some((
                         Instant::now(),
                         PerformingAnimation::new(
                             animations::DynAnimation::new_from_sync(a),
+                            epoch,
                             &mut next_colours,
                             lights,
                         ),
                     ));
                 }
-                super::Command::SyncAnimation(sync) => {
-                    current.animation.sync(sync.clone());
+                super::Command::SyncAnimation(sync, epoch) => {
+                    current.sync(sync, epoch);
+                }
+                super::Command::Resync => {
+                    // the other side may have missed too much to catch up on,
+                    // so start the animation afresh on both sides
+                    let sync = match next.as_ref() {
+                        Some((_, next)) => next.animation.construct_sync(),
+                        None => current.animation.construct_sync(),
+                    };
+                    let epoch = Instant::now();
+
+                    let cmd = DeviceToDevice::SetAnimation(
+                        sync.clone(),
+                        SharedInstant::from_local(epoch),
+                    );
+                    interboard::send_msg(reliable_msg(cmd), 3).await;
+
+                    pending.add(super::Command::SetNextAnimation(sync, epoch));
                 }
             }
         }
@@ -176,14 +452,34 @@ pub async fn rgb_runner(mut driver: Ws2812<'static, PIO1, 0, { NUM_LEDS as usize
                         break;
                     }
                     embassy_futures::select::Either3::Third(_) => {
-                        let corrected_colours =
+                        while let Some(evt) = key_sub.try_next_message_pure() {
+                            current.key_event(evt);
+                            next.key_event(evt);
+                        }
+
+                        let indicators = indicators::State::current();
+                        if indicators != drawn_indicators {
+                            drawn_indicators = indicators;
+                            current.draw(indicators);
+                            next.draw(indicators);
+                        }
+
+                        let level = brightness.level();
+                        let replacement = replacement_frame();
+                        let mut corrected_colours =
                             array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
                                 let mut a = current.colours[i];
                                 let b = next.colours[i];
-                                a.blend(b, ease_fade_on_time(fade_start.elapsed()));
-                                errors[i].process(a)
+                                a.blend(b, ease_fade_on_time(fade_start.elapsed(), FADE_DURATION));
+                                if let Some(frame) = &replacement {
+                                    a = frame[i];
+                                }
+                                let mut a = corrections.apply(i, a);
+                                a.scale(level);
+                                errors[i].process(a, &corrections.gamma)
                             });
 
+                        limit_current(&mut corrected_colours);
                         driver.write(&corrected_colours).await;
                     }
                 }
@@ -193,11 +489,30 @@ pub async fn rgb_runner(mut driver: Ws2812<'static, PIO1, 0, { NUM_LEDS as usize
                         break;
                     }
                     embassy_futures::select::Either::Second(_) => {
-                        let corrected_colours =
+                        while let Some(evt) = key_sub.try_next_message_pure() {
+                            current.key_event(evt);
+                        }
+
+                        let indicators = indicators::State::current();
+                        if indicators != drawn_indicators {
+                            drawn_indicators = indicators;
+                            current.draw(indicators);
+                        }
+
+                        let level = brightness.level();
+                        let replacement = replacement_frame();
+                        let mut corrected_colours =
                             array::from_fn::<_, { NUM_LEDS as usize }, _>(|i| {
-                                errors[i].process(current.colours[i])
+                                let c = match &replacement {
+                                    Some(frame) => frame[i],
+                                    None => current.colours[i],
+                                };
+                                let mut c = corrections.apply(i, c);
+                                c.scale(level);
+                                errors[i].process(c, &corrections.gamma)
                             });
 
+                        limit_current(&mut corrected_colours);
                         driver.write(&corrected_colours).await;
                     }
                 }
@@ -205,291 +520,3 @@ pub async fn rgb_runner(mut driver: Ws2812<'static, PIO1, 0, { NUM_LEDS as usize
         }
     }
 }
-
-#[derive(Default, Clone, Copy)]
-struct GammaErrorTracker {
-    r: U16F16,
-    g: U16F16,
-    b: U16F16,
-}
-
-impl GammaErrorTracker {
-    fn process(&mut self, color: ColorRGB) -> ColorRGB {
-        // color.modify_all(|i| GAMMA[i as usize].int().saturating_to_num());
-        // return color;
-
-        let r = GAMMA[color.r as usize] + self.r;
-        self.r = r.frac();
-        let r = r.int().saturating_to_num();
-
-        let g = GAMMA[color.g as usize] + self.g;
-        self.g = g.frac();
-        let g = g.int().saturating_to_num();
-
-        let b = GAMMA[color.b as usize] + self.b;
-        self.b = b.frac();
-        let b = b.int().saturating_to_num();
-
-        ColorRGB { r, g, b }
-    }
-}
-
-//  ",".join([f"fixed!({255 * ((n / 255) ** 1.9):#.3}: U16F16)" for n in range(256)])
-const GAMMA: [U16F16; 256] = [
-    fixed!(0.00: U16F16),
-    fixed!(0.00683: U16F16),
-    fixed!(0.0255: U16F16),
-    fixed!(0.0550: U16F16),
-    fixed!(0.0951: U16F16),
-    fixed!(0.145: U16F16),
-    fixed!(0.205: U16F16),
-    fixed!(0.275: U16F16),
-    fixed!(0.355: U16F16),
-    fixed!(0.444: U16F16),
-    fixed!(0.542: U16F16),
-    fixed!(0.650: U16F16),
-    fixed!(0.767: U16F16),
-    fixed!(0.892: U16F16),
-    fixed!(1.03: U16F16),
-    fixed!(1.17: U16F16),
-    fixed!(1.32: U16F16),
-    fixed!(1.49: U16F16),
-    fixed!(1.66: U16F16),
-    fixed!(1.84: U16F16),
-    fixed!(2.02: U16F16),
-    fixed!(2.22: U16F16),
-    fixed!(2.43: U16F16),
-    fixed!(2.64: U16F16),
-    fixed!(2.86: U16F16),
-    fixed!(3.09: U16F16),
-    fixed!(3.33: U16F16),
-    fixed!(3.58: U16F16),
-    fixed!(3.83: U16F16),
-    fixed!(4.10: U16F16),
-    fixed!(4.37: U16F16),
-    fixed!(4.65: U16F16),
-    fixed!(4.94: U16F16),
-    fixed!(5.24: U16F16),
-    fixed!(5.55: U16F16),
-    fixed!(5.86: U16F16),
-    fixed!(6.18: U16F16),
-    fixed!(6.51: U16F16),
-    fixed!(6.85: U16F16),
-    fixed!(7.20: U16F16),
-    fixed!(7.55: U16F16),
-    fixed!(7.91: U16F16),
-    fixed!(8.28: U16F16),
-    fixed!(8.66: U16F16),
-    fixed!(9.05: U16F16),
-    fixed!(9.45: U16F16),
-    fixed!(9.85: U16F16),
-    fixed!(10.3: U16F16),
-    fixed!(10.7: U16F16),
-    fixed!(11.1: U16F16),
-    fixed!(11.5: U16F16),
-    fixed!(12.0: U16F16),
-    fixed!(12.4: U16F16),
-    fixed!(12.9: U16F16),
-    fixed!(13.4: U16F16),
-    fixed!(13.8: U16F16),
-    fixed!(14.3: U16F16),
-    fixed!(14.8: U16F16),
-    fixed!(15.3: U16F16),
-    fixed!(15.8: U16F16),
-    fixed!(16.3: U16F16),
-    fixed!(16.8: U16F16),
-    fixed!(17.4: U16F16),
-    fixed!(17.9: U16F16),
-    fixed!(18.4: U16F16),
-    fixed!(19.0: U16F16),
-    fixed!(19.6: U16F16),
-    fixed!(20.1: U16F16),
-    fixed!(20.7: U16F16),
-    fixed!(21.3: U16F16),
-    fixed!(21.9: U16F16),
-    fixed!(22.5: U16F16),
-    fixed!(23.1: U16F16),
-    fixed!(23.7: U16F16),
-    fixed!(24.3: U16F16),
-    fixed!(24.9: U16F16),
-    fixed!(25.6: U16F16),
-    fixed!(26.2: U16F16),
-    fixed!(26.9: U16F16),
-    fixed!(27.5: U16F16),
-    fixed!(28.2: U16F16),
-    fixed!(28.9: U16F16),
-    fixed!(29.5: U16F16),
-    fixed!(30.2: U16F16),
-    fixed!(30.9: U16F16),
-    fixed!(31.6: U16F16),
-    fixed!(32.3: U16F16),
-    fixed!(33.1: U16F16),
-    fixed!(33.8: U16F16),
-    fixed!(34.5: U16F16),
-    fixed!(35.3: U16F16),
-    fixed!(36.0: U16F16),
-    fixed!(36.8: U16F16),
-    fixed!(37.5: U16F16),
-    fixed!(38.3: U16F16),
-    fixed!(39.1: U16F16),
-    fixed!(39.9: U16F16),
-    fixed!(40.6: U16F16),
-    fixed!(41.4: U16F16),
-    fixed!(42.2: U16F16),
-    fixed!(43.1: U16F16),
-    fixed!(43.9: U16F16),
-    fixed!(44.7: U16F16),
-    fixed!(45.6: U16F16),
-    fixed!(46.4: U16F16),
-    fixed!(47.2: U16F16),
-    fixed!(48.1: U16F16),
-    fixed!(49.0: U16F16),
-    fixed!(49.8: U16F16),
-    fixed!(50.7: U16F16),
-    fixed!(51.6: U16F16),
-    fixed!(52.5: U16F16),
-    fixed!(53.4: U16F16),
-    fixed!(54.3: U16F16),
-    fixed!(55.2: U16F16),
-    fixed!(56.2: U16F16),
-    fixed!(57.1: U16F16),
-    fixed!(58.0: U16F16),
-    fixed!(59.0: U16F16),
-    fixed!(59.9: U16F16),
-    fixed!(60.9: U16F16),
-    fixed!(61.9: U16F16),
-    fixed!(62.8: U16F16),
-    fixed!(63.8: U16F16),
-    fixed!(64.8: U16F16),
-    fixed!(65.8: U16F16),
-    fixed!(66.8: U16F16),
-    fixed!(67.8: U16F16),
-    fixed!(68.8: U16F16),
-    fixed!(69.9: U16F16),
-    fixed!(70.9: U16F16),
-    fixed!(71.9: U16F16),
-    fixed!(73.0: U16F16),
-    fixed!(74.0: U16F16),
-    fixed!(75.1: U16F16),
-    fixed!(76.2: U16F16),
-    fixed!(77.2: U16F16),
-    fixed!(78.3: U16F16),
-    fixed!(79.4: U16F16),
-    fixed!(80.5: U16F16),
-    fixed!(81.6: U16F16),
-    fixed!(82.7: U16F16),
-    fixed!(83.8: U16F16),
-    fixed!(85.0: U16F16),
-    fixed!(86.1: U16F16),
-    fixed!(87.2: U16F16),
-    fixed!(88.4: U16F16),
-    fixed!(89.5: U16F16),
-    fixed!(90.7: U16F16),
-    fixed!(91.9: U16F16),
-    fixed!(93.0: U16F16),
-    fixed!(94.2: U16F16),
-    fixed!(95.4: U16F16),
-    fixed!(96.6: U16F16),
-    fixed!(97.8: U16F16),
-    fixed!(99.0: U16F16),
-    fixed!(1.00e+02: U16F16),
-    fixed!(1.01e+02: U16F16),
-    fixed!(1.03e+02: U16F16),
-    fixed!(1.04e+02: U16F16),
-    fixed!(1.05e+02: U16F16),
-    fixed!(1.06e+02: U16F16),
-    fixed!(1.08e+02: U16F16),
-    fixed!(1.09e+02: U16F16),
-    fixed!(1.10e+02: U16F16),
-    fixed!(1.12e+02: U16F16),
-    fixed!(1.13e+02: U16F16),
-    fixed!(1.14e+02: U16F16),
-    fixed!(1.15e+02: U16F16),
-    fixed!(1.17e+02: U16F16),
-    fixed!(1.18e+02: U16F16),
-    fixed!(1.19e+02: U16F16),
-    fixed!(1.21e+02: U16F16),
-    fixed!(1.22e+02: U16F16),
-    fixed!(1.23e+02: U16F16),
-    fixed!(1.25e+02: U16F16),
-    fixed!(1.26e+02: U16F16),
-    fixed!(1.27e+02: U16F16),
-    fixed!(1.29e+02: U16F16),
-    fixed!(1.30e+02: U16F16),
-    fixed!(1.32e+02: U16F16),
-    fixed!(1.33e+02: U16F16),
-    fixed!(1.34e+02: U16F16),
-    fixed!(1.36e+02: U16F16),
-    fixed!(1.37e+02: U16F16),
-    fixed!(1.39e+02: U16F16),
-    fixed!(1.40e+02: U16F16),
-    fixed!(1.41e+02: U16F16),
-    fixed!(1.43e+02: U16F16),
-    fixed!(1.44e+02: U16F16),
-    fixed!(1.46e+02: U16F16),
-    fixed!(1.47e+02: U16F16),
-    fixed!(1.49e+02: U16F16),
-    fixed!(1.50e+02: U16F16),
-    fixed!(1.52e+02: U16F16),
-    fixed!(1.53e+02: U16F16),
-    fixed!(1.55e+02: U16F16),
-    fixed!(1.56e+02: U16F16),
-    fixed!(1.58e+02: U16F16),
-    fixed!(1.59e+02: U16F16),
-    fixed!(1.61e+02: U16F16),
-    fixed!(1.62e+02: U16F16),
-    fixed!(1.64e+02: U16F16),
-    fixed!(1.65e+02: U16F16),
-    fixed!(1.67e+02: U16F16),
-    fixed!(1.68e+02: U16F16),
-    fixed!(1.70e+02: U16F16),
-    fixed!(1.72e+02: U16F16),
-    fixed!(1.73e+02: U16F16),
-    fixed!(1.75e+02: U16F16),
-    fixed!(1.76e+02: U16F16),
-    fixed!(1.78e+02: U16F16),
-    fixed!(1.80e+02: U16F16),
-    fixed!(1.81e+02: U16F16),
-    fixed!(1.83e+02: U16F16),
-    fixed!(1.84e+02: U16F16),
-    fixed!(1.86e+02: U16F16),
-    fixed!(1.88e+02: U16F16),
-    fixed!(1.89e+02: U16F16),
-    fixed!(1.91e+02: U16F16),
-    fixed!(1.93e+02: U16F16),
-    fixed!(1.94e+02: U16F16),
-    fixed!(1.96e+02: U16F16),
-    fixed!(1.98e+02: U16F16),
-    fixed!(1.99e+02: U16F16),
-    fixed!(2.01e+02: U16F16),
-    fixed!(2.03e+02: U16F16),
-    fixed!(2.04e+02: U16F16),
-    fixed!(2.06e+02: U16F16),
-    fixed!(2.08e+02: U16F16),
-    fixed!(2.10e+02: U16F16),
-    fixed!(2.11e+02: U16F16),
-    fixed!(2.13e+02: U16F16),
-    fixed!(2.15e+02: U16F16),
-    fixed!(2.17e+02: U16F16),
-    fixed!(2.18e+02: U16F16),
-    fixed!(2.20e+02: U16F16),
-    fixed!(2.22e+02: U16F16),
-    fixed!(2.24e+02: U16F16),
-    fixed!(2.25e+02: U16F16),
-    fixed!(2.27e+02: U16F16),
-    fixed!(2.29e+02: U16F16),
-    fixed!(2.31e+02: U16F16),
-    fixed!(2.33e+02: U16F16),
-    fixed!(2.35e+02: U16F16),
-    fixed!(2.36e+02: U16F16),
-    fixed!(2.38e+02: U16F16),
-    fixed!(2.40e+02: U16F16),
-    fixed!(2.42e+02: U16F16),
-    fixed!(2.44e+02: U16F16),
-    fixed!(2.46e+02: U16F16),
-    fixed!(2.47e+02: U16F16),
-    fixed!(2.49e+02: U16F16),
-    fixed!(2.51e+02: U16F16),
-    fixed!(2.53e+02: U16F16),
-    fixed!(2.55e+02: U16F16),
-];
@@ -0,0 +1,114 @@
+//! Receiving animation programs from the host and keeping them in flash
+//!
+//! Programs are small, so they're collected in memory and only written out
+//! once the whole thing has arrived and checks out.
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use serde::{Deserialize, Serialize};
+use shared::{
+    device_to_host::{DeviceToHostMsg, ScriptUploadError, ScriptUploadStatus},
+    host_to_device::ScriptUpload,
+    script::MAX_PROGRAM_LEN,
+};
+
+use crate::{
+    flash,
+    messages::{distributors::MessageProvenance, reliable_msg, send_to_host},
+    utils::log,
+};
+
+use super::animations::script;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct StoredProgram(heapless::Vec<u8, MAX_PROGRAM_LEN>);
+
+struct InProgress {
+    len: u16,
+    code: heapless::Vec<u8, MAX_PROGRAM_LEN>,
+}
+
+static UPLOAD: Mutex<ThreadModeRawMutex, Option<InProgress>> = Mutex::new(None);
+
+pub async fn init() {
+    if let Some(StoredProgram(code)) = flash::get::<StoredProgram>().await {
+        match script::install(&code) {
+            Ok(hash) => log::info!("Loaded animation program {:x}", hash),
+            Err(e) => log::warn!("Stored animation program is invalid: {:?}", e),
+        }
+    }
+}
+
+pub async fn handle(upload: ScriptUpload) {
+    let status = match step(&mut *UPLOAD.lock().await, upload) {
+        Ok(status) => status,
+        Err(e) => {
+            log::warn!("Animation program upload failed: {:?}", e);
+            ScriptUploadStatus::Failed(e)
+        }
+    };
+
+    send_to_host(
+        reliable_msg(DeviceToHostMsg::ScriptUpload(status)),
+        MessageProvenance::Origin,
+    )
+    .await;
+}
+
+fn step(
+    state: &mut Option<InProgress>,
+    upload: ScriptUpload,
+) -> Result<ScriptUploadStatus, ScriptUploadError> {
+    match upload {
+        ScriptUpload::Begin { len } => {
+            *state = None;
+
+            if len as usize > MAX_PROGRAM_LEN {
+                return Err(ScriptUploadError::TooLarge);
+            }
+
+            *state = Some(InProgress {
+                len,
+                code: heapless::Vec::new(),
+            });
+
+            Ok(ScriptUploadStatus::Ready)
+        }
+        ScriptUpload::Chunk { offset, data } => {
+            let s = state.as_mut().ok_or(ScriptUploadError::NotStarted)?;
+            let written = s.code.len() as u16;
+            let end = offset.saturating_add(data.len() as u16);
+
+            if end <= written {
+                // a resend of something we already have
+                return Ok(ScriptUploadStatus::Written { upto: written });
+            }
+
+            if offset != written {
+                return Err(ScriptUploadError::OutOfOrder { expected: written });
+            }
+
+            if end > s.len {
+                return Err(ScriptUploadError::TooLarge);
+            }
+
+            s.code
+                .extend_from_slice(&data)
+                .map_err(|_| ScriptUploadError::TooLarge)?;
+
+            Ok(ScriptUploadStatus::Written { upto: end })
+        }
+        ScriptUpload::Commit => {
+            let s = state.take().ok_or(ScriptUploadError::NotStarted)?;
+
+            if s.code.len() != s.len as usize {
+                return Err(ScriptUploadError::Incomplete);
+            }
+
+            let hash = script::install(&s.code).map_err(ScriptUploadError::Invalid)?;
+
+            flash::save_later(&StoredProgram(s.code));
+
+            Ok(ScriptUploadStatus::Installed { hash })
+        }
+    }
+}
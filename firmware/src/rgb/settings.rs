@@ -0,0 +1,61 @@
+//! Animation choices made by the host, kept across reboots
+//!
+//! Both sides keep a copy so that whichever ends up primary knows what to
+//! show, only the primary side acts on them.
+
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, mutex::Mutex};
+use shared::host_to_device::AnimationSettings;
+
+use crate::{flash, side, utils::log};
+
+use super::{
+    animation::Animation,
+    animations::{AnimationSync, DynAnimation},
+    playlist, show,
+};
+
+static SETTINGS: Mutex<ThreadModeRawMutex, AnimationSettings> = Mutex::new(AnimationSettings {
+    animation: None,
+    randomise: true,
+});
+
+pub async fn init() {
+    if let Some(s) = flash::get::<AnimationSettings>().await {
+        log::info!("Loaded animation settings: {:?}", s);
+        *SETTINGS.lock().await = s;
+    }
+}
+
+pub async fn randomise() -> bool {
+    SETTINGS.lock().await.randomise
+}
+
+/// The animation to start with, from the playlist or the chosen one if there
+/// is one
+pub async fn initial_animation() -> DynAnimation {
+    let choice = match playlist::initial_animation().await {
+        Some(choice) => Some(choice),
+        None => SETTINGS.lock().await.animation,
+    };
+
+    match choice {
+        Some(choice) => DynAnimation::new_from_sync(AnimationSync::from(choice)),
+        None => DynAnimation::random(),
+    }
+}
+
+/// Apply settings from the host, switching animation straight away if one was
+/// chosen
+pub async fn update(settings: AnimationSettings) {
+    *SETTINGS.lock().await = settings;
+
+    flash::save_later(&settings);
+
+    if !side::is_primary() {
+        return;
+    }
+
+    if let Some(choice) = settings.animation {
+        show(AnimationSync::from(choice)).await;
+    }
+}
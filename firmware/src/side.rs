@@ -1,12 +1,38 @@
+use core::future::Future;
+
+use embassy_futures::select::select;
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, pubsub::PubSubChannel};
 use portable_atomic::AtomicBool;
 use shared::side::KeyboardSide;
 
+use crate::utils::log;
+
 static SIDE_IS_LEFT: AtomicBool = AtomicBool::new(false);
 static HAS_USB: AtomicBool = AtomicBool::new(false);
+static IS_PRIMARY: AtomicBool = AtomicBool::new(false);
+
+/// Which half is in charge of talking to the host
+///
+/// Exactly one half is primary while the link is up, it runs the layout,
+/// sends reports to the host and drives the shared state (animations etc). The
+/// other half acts as a peripheral and forwards everything to it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "probe", derive(defmt::Format))]
+pub enum Role {
+    Primary,
+    Secondary,
+}
+
+/// Published to whenever usb is plugged into or unplugged from this side
+pub static USB_CHANGES: PubSubChannel<ThreadModeRawMutex, bool, 1, 4, 1> = PubSubChannel::new();
+
+/// Published to whenever this side changes role
+pub static ROLE_CHANGES: PubSubChannel<ThreadModeRawMutex, Role, 2, 12, 1> = PubSubChannel::new();
 
 pub fn init(side: KeyboardSide, has_usb: bool) {
     SIDE_IS_LEFT.store(side.is_left(), portable_atomic::Ordering::Relaxed);
     HAS_USB.store(has_usb, portable_atomic::Ordering::Relaxed);
+    IS_PRIMARY.store(has_usb, portable_atomic::Ordering::Relaxed);
 }
 
 pub fn is_this_side(side: KeyboardSide) -> bool {
@@ -25,6 +51,57 @@ pub fn get_other_side() -> KeyboardSide {
     get_side().other()
 }
 
+/// Whether this side is physically connected to a host, see [`is_primary`] for
+/// whether it should actually be talking to it
 pub fn this_side_has_usb() -> bool {
     HAS_USB.load(portable_atomic::Ordering::Relaxed)
 }
+
+pub(crate) fn set_has_usb(has_usb: bool) {
+    if HAS_USB.swap(has_usb, portable_atomic::Ordering::Relaxed) != has_usb {
+        log::info!("Usb connected? {}", has_usb);
+        USB_CHANGES.immediate_publisher().publish_immediate(has_usb);
+    }
+}
+
+pub fn is_primary() -> bool {
+    IS_PRIMARY.load(portable_atomic::Ordering::Relaxed)
+}
+
+pub fn role() -> Role {
+    if is_primary() {
+        Role::Primary
+    } else {
+        Role::Secondary
+    }
+}
+
+pub(crate) fn set_role(role: Role) {
+    let primary = role == Role::Primary;
+
+    if IS_PRIMARY.swap(primary, portable_atomic::Ordering::Relaxed) != primary {
+        log::info!("This side is now {:?}", role);
+        ROLE_CHANGES.immediate_publisher().publish_immediate(role);
+    }
+}
+
+/// Run a task only while this side is primary
+///
+/// The future is dropped when this side stops being primary, and started
+/// afresh if it becomes primary again.
+pub async fn while_primary<F: Future<Output = ()>>(mut f: impl FnMut() -> F) {
+    let mut sub = ROLE_CHANGES.subscriber().unwrap();
+
+    loop {
+        while !is_primary() {
+            sub.next_message_pure().await;
+        }
+
+        select(f(), async {
+            while is_primary() {
+                sub.next_message_pure().await;
+            }
+        })
+        .await;
+    }
+}
@@ -57,6 +57,8 @@ async fn trackpad_task(spi: TrackpadSpi) {
     loop {
         match trackpad.get_report().await {
             Ok(Some(report)) => {
+                crate::idle::activity();
+
                 let rep = MouseReport {
                     x: report.0,
                     y: report.1,
@@ -8,7 +8,7 @@ use embassy_usb::Builder;
 use shared::device_to_host::DeviceToHost;
 use shared::host_to_device::HostToDevice;
 
-use crate::messages::transmissions;
+use crate::messages::transmissions::{self, TransmissionStats};
 use crate::messages::TransmittedMessage;
 use crate::utils;
 
@@ -19,6 +19,8 @@ pub static COMMANDS_TO_HOST: Channel<CS, TransmittedMessage<DeviceToHost>, 16> =
 
 const BUF_SIZE: usize = 128;
 
+static SERIAL_STATS: TransmissionStats = TransmissionStats::new();
+
 #[embassy_executor::task]
 async fn serial_in_task(
     out_pipe: Writer<'static, CS, BUF_SIZE>,
@@ -57,7 +59,7 @@ async fn eventer_task(tx: Writer<'static, CS, BUF_SIZE>, rx: Reader<'static, CS,
     let tx_fn = |e| async {
         msg_pub.publish(e).await;
     };
-    transmissions::eventer(tx, rx, rx_fn, tx_fn).await;
+    transmissions::eventer(tx, rx, rx_fn, tx_fn, &SERIAL_STATS).await;
 }
 
 pub fn init(spawner: &Spawner, builder: &mut Builder<'static, USBDriver>) {
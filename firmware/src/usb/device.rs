@@ -1,10 +1,33 @@
+use embassy_futures::select::{select, Either};
+use embassy_sync::{blocking_mutex::raw::ThreadModeRawMutex, signal::Signal};
 use embassy_usb::driver::Driver;
-use embassy_usb::{Builder, Config};
+use embassy_usb::{Builder, Config, Handler, UsbDevice};
+
+use crate::{power, side, utils::log};
 
 use super::USBDriver;
 
 pub const MAX_PACKET_SIZE: u16 = 64;
 
+static WAKE_HOST: Signal<ThreadModeRawMutex, ()> = Signal::new();
+
+/// Ask a suspended host to wake up
+pub fn wake_host() {
+    WAKE_HOST.signal(());
+}
+
+struct PowerHandler;
+
+impl Handler for PowerHandler {
+    fn suspended(&mut self, suspended: bool) {
+        power::usb_suspended(suspended);
+    }
+
+    fn reset(&mut self) {
+        power::usb_suspended(false);
+    }
+}
+
 pub fn init_usb<'d, D: Driver<'d>>(driver: D) -> Builder<'d, D> {
     let mut config = Config::new(0x2e8a, 0x000a);
     config.manufacturer = Some("Ben Simms");
@@ -12,19 +35,65 @@ pub fn init_usb<'d, D: Driver<'d>>(driver: D) -> Builder<'d, D> {
     config.serial_number = None;
     config.max_power = 500;
     config.max_packet_size_0 = MAX_PACKET_SIZE as u8;
+    config.supports_remote_wakeup = true;
 
-    Builder::new(
+    let mut builder = Builder::new(
         driver,
         config,
         static_cell::make_static!([0; 256]),
         static_cell::make_static!([0; 256]),
         static_cell::make_static!([0; 256]),
         static_cell::make_static!([0; 256]),
-    )
+    );
+
+    builder.handler(static_cell::make_static!(PowerHandler));
+
+    builder
 }
 
 #[embassy_executor::task]
 pub async fn run_usb(builder: Builder<'static, USBDriver>) {
     let mut device = builder.build();
-    device.run().await;
+    let mut role_sub = side::ROLE_CHANGES.subscriber().unwrap();
+
+    loop {
+        // only the primary side talks to the host, the other stays off the
+        // bus even when plugged in. Being primary also means VBUS is there,
+        // which the controller can't see for itself.
+        while !side::is_primary() {
+            role_sub.next_message_pure().await;
+        }
+
+        log::info!("Starting usb");
+
+        let demoted = async {
+            while side::is_primary() {
+                role_sub.next_message_pure().await;
+            }
+        };
+
+        select(serve(&mut device), demoted).await;
+
+        log::info!("No longer primary, stopping usb");
+        device.disable().await;
+    }
+}
+
+/// Run the device, following the host in and out of suspend
+async fn serve(device: &mut UsbDevice<'static, USBDriver>) {
+    loop {
+        device.run_until_suspend().await;
+
+        // anything from before the suspend is stale
+        WAKE_HOST.reset();
+
+        match select(device.wait_resume(), WAKE_HOST.wait()).await {
+            Either::First(()) => {}
+            Either::Second(()) => {
+                if device.remote_wakeup().await.is_err() {
+                    log::warn!("The host didn't allow us to wake it up");
+                }
+            }
+        }
+    }
 }
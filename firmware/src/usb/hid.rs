@@ -1,7 +1,11 @@
 use embassy_executor::Spawner;
 use embassy_futures::yield_now;
 use embassy_sync::channel::Channel;
-use embassy_usb::{class::hid::HidWriter, Builder};
+use embassy_usb::{
+    class::hid::{HidWriter, ReportId, RequestHandler},
+    control::OutResponse,
+    Builder,
+};
 use num::Integer;
 use packed_struct::PackedStruct;
 use portable_atomic::{AtomicBool, AtomicU8};
@@ -34,6 +38,25 @@ pub async fn publish_keyboard_report(report: NKROBootKeyboardReport) {
 
 static MOUSE_BUTTON_STATE: AtomicU8 = AtomicU8::new(0);
 static IS_SCROLLING: AtomicBool = AtomicBool::new(false);
+static CAPS_LOCK: AtomicBool = AtomicBool::new(false);
+
+/// Whether the host has caps lock on, going by the leds it last set
+pub fn caps_lock() -> bool {
+    CAPS_LOCK.load(portable_atomic::Ordering::Relaxed)
+}
+
+/// Receives the keyboard led output report
+struct KeyboardLeds;
+
+impl RequestHandler for KeyboardLeds {
+    fn set_report(&mut self, _id: ReportId, data: &[u8]) -> OutResponse {
+        if let Some(&leds) = data.first() {
+            CAPS_LOCK.store(leds & 0b10 != 0, portable_atomic::Ordering::Relaxed);
+        }
+
+        OutResponse::Accepted
+    }
+}
 
 #[embassy_executor::task]
 async fn handle_mouse_clicks() {
@@ -50,9 +73,13 @@ async fn handle_mouse_clicks() {
 
             MOUSE_BUTTON_STATE.store(buttons, portable_atomic::Ordering::SeqCst);
             IS_SCROLLING.store(b.scrolling(), portable_atomic::Ordering::SeqCst);
-            MOUSE_REPORTS
-                .send(shared::hid::MouseReport::default())
-                .await;
+
+            // nobody might be reading reports on the secondary side
+            if side::is_primary() {
+                MOUSE_REPORTS
+                    .send(shared::hid::MouseReport::default())
+                    .await;
+            }
         }
     }
 }
@@ -181,7 +208,7 @@ pub fn init(spawner: &Spawner, builder: &mut Builder<'static, USBDriver>) {
         keyboard_state,
         embassy_usb::class::hid::Config {
             report_descriptor: NKRO_BOOT_KEYBOARD_REPORT_DESCRIPTOR,
-            request_handler: None,
+            request_handler: Some(static_cell::make_static!(KeyboardLeds)),
             poll_ms: 10,
             max_packet_size: 64,
         },
@@ -191,13 +218,13 @@ pub fn init(spawner: &Spawner, builder: &mut Builder<'static, USBDriver>) {
     spawner.must_spawn(keyboard_writer(keyboard_hid_writer));
     spawner.must_spawn(handle_mouse_clicks());
 
-    if side::this_side_has_usb() && side::is_this_side(shared::side::KeyboardSide::Left) {
+    if side::is_this_side(shared::side::KeyboardSide::Left) {
         spawner.must_spawn(interboard_receiver());
     }
 }
 
 pub async fn send_mouse_hid_to_host(report: shared::hid::MouseReport) {
-    if side::this_side_has_usb() {
+    if side::is_primary() {
         publish_mouse_report(report).await;
     } else {
         let msg = DeviceToDevice::ForwardedToHostMouse(report);
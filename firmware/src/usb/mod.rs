@@ -1,4 +1,5 @@
 use embassy_executor::Spawner;
+use embassy_rp::gpio::Input;
 use embassy_rp::peripherals::USB;
 use embassy_rp::usb::Driver;
 use embassy_sync::channel::TrySendError;
@@ -6,7 +7,7 @@ use embassy_sync::channel::TrySendError;
 use shared::device_to_host::DeviceToHost;
 
 pub use channel::COMMANDS_FROM_HOST;
-pub use device::MAX_PACKET_SIZE;
+pub use device::{wake_host, MAX_PACKET_SIZE};
 pub use hid::publish_mouse_report;
 
 use crate::messages::TransmittedMessage;
@@ -16,6 +17,7 @@ pub mod channel;
 pub mod device;
 pub mod hid;
 pub mod picotool;
+pub mod vbus;
 
 pub type USBDriver = impl embassy_usb::driver::Driver<'static>;
 
@@ -33,9 +35,11 @@ fn set_guesser(driver: Driver<'static, USB>) -> USBDriver {
     guesser.wrap_driver(driver)
 }
 
-pub fn init(spawner: &Spawner, driver: Driver<'static, USB>) {
+pub fn init(spawner: &Spawner, driver: Driver<'static, USB>, vbus_pin: Input<'static>) {
     log::info!("Initializing usb");
 
+    vbus::init(spawner, vbus_pin);
+
     let driver = set_guesser(driver);
     let mut builder = device::init_usb(driver);
 
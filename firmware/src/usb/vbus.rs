@@ -0,0 +1,27 @@
+use embassy_executor::Spawner;
+use embassy_rp::gpio::Input;
+use embassy_time::{Duration, Timer};
+
+use crate::side;
+
+/// How long VBUS has to settle before a plug or unplug is believed
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+pub fn init(spawner: &Spawner, pin: Input<'static>) {
+    spawner.must_spawn(vbus_monitor(pin));
+}
+
+#[embassy_executor::task]
+async fn vbus_monitor(mut pin: Input<'static>) {
+    loop {
+        if side::this_side_has_usb() {
+            pin.wait_for_low().await;
+        } else {
+            pin.wait_for_high().await;
+        }
+
+        Timer::after(DEBOUNCE).await;
+
+        side::set_has_usb(pin.is_high());
+    }
+}
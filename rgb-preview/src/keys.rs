@@ -0,0 +1,4 @@
+//! The parts of the firmware's keys module the animations use
+
+#[path = "../../firmware/src/keys/matrix.rs"]
+pub mod matrix;
@@ -0,0 +1,334 @@
+//! Renders the rgb animations without needing to flash a keyboard
+//!
+//! Both halves run their own copy of the animation from the same sync message,
+//! just as they do on the keyboard, and are stepped at the animation's tick
+//! rate. Output goes through the same crossfade, colour correction and gamma
+//! dithering as the firmware's runner (with the default calibration), at the
+//! same 1kHz, and is averaged into each frame.
+
+#![feature(
+    iter_repeat_n,
+    maybe_uninit_uninit_array,
+    const_maybe_uninit_uninit_array,
+    maybe_uninit_array_assume_init,
+    const_maybe_uninit_array_assume_init,
+    const_mut_refs,
+    const_maybe_uninit_write,
+    option_take_if
+)]
+
+use std::path::PathBuf;
+
+use cichlid::ColorRGB;
+use clap::{Parser, ValueEnum};
+use embassy_time::Duration;
+use keyberon::layout::Event;
+use rand::Rng;
+use shared::{
+    host_to_device::{AnimationChoice, PerlinColours},
+    side::KeyboardSide,
+};
+
+use rgb::{
+    animation::Animation,
+    animations::{AnimationSync, DynAnimation},
+    blend::{
+        ease_fade_on_time, Gamma, GammaErrorTracker, COLOUR_CORRECTION, DEFAULT_GAMMA,
+        FADE_DURATION,
+    },
+    layout::{self, Light, NUM_LEDS},
+};
+use rng::MyRng;
+
+mod keys;
+mod metrics;
+mod render;
+mod rgb;
+mod rng;
+mod side;
+
+/// How often the runner writes to the leds
+const OUTPUT_PERIOD: Duration = Duration::from_hz(1000);
+
+#[derive(Parser)]
+#[command(about = "Render an rgb animation to png frames or a gif")]
+struct Args {
+    animation: Choice,
+
+    /// Crossfade into this animation halfway through
+    #[arg(long)]
+    then: Option<Choice>,
+
+    /// Where to write to, a gif if it ends in `.gif` and a directory of pngs
+    /// otherwise
+    #[arg(short, long, default_value = "preview.gif")]
+    output: PathBuf,
+
+    #[arg(long, default_value_t = 10)]
+    seconds: u64,
+
+    #[arg(long, default_value_t = 30)]
+    fps: u64,
+
+    #[arg(long, default_value_t = 255)]
+    brightness: u8,
+
+    /// Gamma exponent, in tenths
+    #[arg(long, default_value_t = DEFAULT_GAMMA)]
+    gamma: u8,
+
+    /// Pixels per millimetre
+    #[arg(long, default_value_t = 2)]
+    scale: u32,
+
+    /// How often to press a random key, for animations that react to typing
+    #[arg(long, default_value_t = 3)]
+    presses_per_sec: u64,
+
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// An animation program to run, see `shared::script`
+    #[arg(long)]
+    script: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Choice {
+    Off,
+    Snow,
+    Perlin,
+    Rain,
+    Typing,
+    Heatmap,
+    /// The program given with `--script`
+    Script,
+}
+
+impl From<Choice> for AnimationChoice {
+    fn from(choice: Choice) -> Self {
+        match choice {
+            Choice::Off => AnimationChoice::Off,
+            Choice::Snow => AnimationChoice::Snow,
+            Choice::Perlin => AnimationChoice::Perlin(PerlinColours::Random),
+            Choice::Rain => AnimationChoice::Rain { colour: None },
+            Choice::Typing => AnimationChoice::Typing { colour: None },
+            Choice::Heatmap => AnimationChoice::Heatmap,
+            Choice::Script => AnimationChoice::Script,
+        }
+    }
+}
+
+/// An animation along with when it next ticks, like the runner's
+/// `PerformingAnimation`
+struct Performing {
+    animation: DynAnimation,
+    next_tick: Duration,
+    colours: [ColorRGB; NUM_LEDS as usize],
+}
+
+impl Performing {
+    fn new(sync: AnimationSync, side: KeyboardSide, lights: &[Light], now: Duration) -> Self {
+        let animation = side::as_side(side, || DynAnimation::new_from_sync(sync));
+
+        let mut performing = Self {
+            next_tick: now + animation.tick_rate(),
+            animation,
+            colours: [ColorRGB::Black; NUM_LEDS as usize],
+        };
+        performing.render(side, lights);
+        performing
+    }
+
+    fn step(&mut self, side: KeyboardSide, lights: &[Light], now: Duration) {
+        if now >= self.next_tick {
+            self.next_tick += self.animation.tick_rate();
+            self.render(side, lights);
+        }
+    }
+
+    fn render(&mut self, side: KeyboardSide, lights: &[Light]) {
+        side::as_side(side, || {
+            self.animation.tick();
+
+            for (dest, light) in self.colours.iter_mut().zip(lights) {
+                *dest = self.animation.render(light);
+            }
+        });
+    }
+}
+
+struct Half {
+    side: KeyboardSide,
+    lights: &'static [Light; NUM_LEDS as usize],
+    current: Performing,
+    next: Option<(Duration, Performing)>,
+    errors: [GammaErrorTracker; NUM_LEDS as usize],
+    /// What was sent to each led over the current frame, summed
+    totals: [[u32; 3]; NUM_LEDS as usize],
+}
+
+impl Half {
+    fn new(side: KeyboardSide, sync: AnimationSync) -> Self {
+        let lights = match side {
+            KeyboardSide::Left => &layout::LEFT,
+            KeyboardSide::Right => &layout::RIGHT,
+        };
+
+        Self {
+            side,
+            lights,
+            current: Performing::new(sync, side, lights, Duration::from_ticks(0)),
+            next: None,
+            errors: [GammaErrorTracker::default(); NUM_LEDS as usize],
+            totals: [[0; 3]; NUM_LEDS as usize],
+        }
+    }
+
+    fn switch_to(&mut self, sync: AnimationSync, now: Duration) {
+        let next = Performing::new(sync, self.side, self.lights, now);
+        self.next = Some((now, next));
+    }
+
+    fn key_event(&mut self, event: Event) {
+        side::as_side(self.side, || {
+            self.current.animation.key_event(event);
+
+            if let Some((_, next)) = &mut self.next {
+                next.animation.key_event(event);
+            }
+        });
+    }
+
+    /// Advance to `now` and write to the leds, as the runner does every
+    /// millisecond
+    fn output(&mut self, now: Duration, brightness: u8, gamma: &Gamma) {
+        if self
+            .next
+            .as_ref()
+            .is_some_and(|(start, _)| now - *start > FADE_DURATION)
+        {
+            self.current = self.next.take().unwrap().1;
+        }
+
+        self.current.step(self.side, self.lights, now);
+
+        if let Some((_, next)) = &mut self.next {
+            next.step(self.side, self.lights, now);
+        }
+
+        for i in 0..NUM_LEDS as usize {
+            let mut c = self.current.colours[i];
+
+            if let Some((start, next)) = &self.next {
+                c.blend(
+                    next.colours[i],
+                    ease_fade_on_time(now - *start, FADE_DURATION),
+                );
+            }
+
+            // the firmware's default calibration only corrects the switches
+            if self.lights[i].kind == layout::Kind::Switch {
+                c.scale_from_other(COLOUR_CORRECTION);
+            }
+
+            c.scale(brightness);
+            let c = self.errors[i].process(c, gamma);
+
+            self.totals[i][0] += c.r as u32;
+            self.totals[i][1] += c.g as u32;
+            self.totals[i][2] += c.b as u32;
+        }
+    }
+
+    /// The average of what was sent to the leds since the last frame
+    fn take_frame(&mut self, outputs: u32) -> [[u8; 3]; NUM_LEDS as usize] {
+        let frame = self.totals.map(|t| t.map(|c| (c / outputs.max(1)) as u8));
+        self.totals = [[0; 3]; NUM_LEDS as usize];
+        frame
+    }
+}
+
+/// A press and release of a random switch on either side
+fn random_press() -> [Event; 2] {
+    let lights = layout::LEFT.iter().chain(layout::RIGHT.iter());
+    let switches = lights.filter_map(|l| l.position).collect::<Vec<_>>();
+    let (col, row) = switches[MyRng.gen_range(0..switches.len())];
+
+    [Event::Press(row, col), Event::Release(row, col)]
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
+
+    rng::seed(args.seed);
+
+    if let Some(path) = &args.script {
+        let code = std::fs::read(path)?;
+        let hash = rgb::animations::script::install(&code)
+            .map_err(|e| format!("invalid program: {e:?}"))?;
+        println!("Loaded program {hash:08x}");
+    }
+
+    let sync = AnimationSync::from(AnimationChoice::from(args.animation));
+    let mut halves = [
+        Half::new(KeyboardSide::Left, sync.clone()),
+        Half::new(KeyboardSide::Right, sync),
+    ];
+
+    let end = Duration::from_secs(args.seconds);
+    let switch_at = Duration::from_ticks(end.as_ticks() / 2);
+    let frame_period = Duration::from_hz(args.fps);
+    let press_period = (args.presses_per_sec > 0).then(|| Duration::from_hz(args.presses_per_sec));
+
+    let gamma = Gamma::new(args.gamma);
+
+    let mut renderer = render::Renderer::new(args.scale, frame_period, &args.output)?;
+    let mut next_frame = frame_period;
+    let mut next_press = press_period.unwrap_or(end);
+    let mut outputs = 0;
+
+    let mut then = args.then;
+
+    let mut now = Duration::from_ticks(0);
+    while now < end {
+        if let Some(then) = then.take_if(|_| now >= switch_at) {
+            let sync = AnimationSync::from(AnimationChoice::from(then));
+            for half in &mut halves {
+                half.switch_to(sync.clone(), now);
+            }
+        }
+
+        if let Some(period) = press_period.filter(|_| now >= next_press) {
+            next_press += period;
+
+            // both sides see every key event
+            for event in random_press() {
+                for half in &mut halves {
+                    half.key_event(event);
+                }
+            }
+        }
+
+        for half in &mut halves {
+            half.output(now, args.brightness, &gamma);
+        }
+        outputs += 1;
+
+        if now >= next_frame {
+            next_frame += frame_period;
+
+            let [left, right] = &mut halves;
+            let frame = [
+                (left.lights, left.take_frame(outputs)),
+                (right.lights, right.take_frame(outputs)),
+            ];
+            renderer.push(&frame)?;
+            outputs = 0;
+        }
+
+        now += OUTPUT_PERIOD;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Drawing the leds where they sit on the keyboard
+
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use embassy_time::Duration;
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, Rgba, RgbaImage,
+};
+
+use crate::rgb::layout::{self, Kind, Light, NUM_LEDS};
+
+/// Space left around the outermost leds (mm)
+const MARGIN: i16 = 20;
+
+const BACKGROUND: Rgba<u8> = Rgba([16, 16, 20, 255]);
+
+pub type HalfFrame = (
+    &'static [Light; NUM_LEDS as usize],
+    [[u8; 3]; NUM_LEDS as usize],
+);
+
+enum Output {
+    Gif(GifEncoder<File>, Delay),
+    Pngs(PathBuf, usize),
+}
+
+/// Frames are written out as they're drawn, holding on to them all would take
+/// an awful lot of memory
+pub struct Renderer {
+    scale: u32,
+    /// Bottom left and top right of the keyboard (mm)
+    bounds: ((i16, i16), (i16, i16)),
+    output: Output,
+}
+
+impl Renderer {
+    pub fn new(
+        scale: u32,
+        frame_period: Duration,
+        path: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let locations = layout::LEFT
+            .iter()
+            .chain(layout::RIGHT.iter())
+            .map(|l| l.location);
+
+        let (mut min, mut max) = ((i16::MAX, i16::MAX), (i16::MIN, i16::MIN));
+        for (x, y) in locations {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+
+        let output = if path.extension().is_some_and(|e| e == "gif") {
+            let mut encoder = GifEncoder::new(File::create(path)?);
+            encoder.set_repeat(Repeat::Infinite)?;
+
+            let delay = Delay::from_numer_denom_ms(frame_period.as_micros() as u32, 1000);
+            Output::Gif(encoder, delay)
+        } else {
+            std::fs::create_dir_all(path)?;
+            Output::Pngs(path.to_owned(), 0)
+        };
+
+        Ok(Self {
+            scale,
+            bounds: (
+                (min.0 - MARGIN, min.1 - MARGIN),
+                (max.0 + MARGIN, max.1 + MARGIN),
+            ),
+            output,
+        })
+    }
+
+    fn size(&self) -> (u32, u32) {
+        let ((x0, y0), (x1, y1)) = self.bounds;
+        ((x1 - x0) as u32 * self.scale, (y1 - y0) as u32 * self.scale)
+    }
+
+    /// Where a light sits in the image, led locations have y going up
+    fn to_pixel(&self, (x, y): (i16, i16)) -> (f32, f32) {
+        let ((x0, _), (_, y1)) = self.bounds;
+        let scale = self.scale as f32;
+
+        ((x - x0) as f32 * scale, (y1 - y) as f32 * scale)
+    }
+
+    pub fn push(&mut self, halves: &[HalfFrame]) -> Result<(), Box<dyn std::error::Error>> {
+        let (width, height) = self.size();
+        let mut image = RgbaImage::from_pixel(width, height, BACKGROUND);
+
+        for (lights, colours) in halves {
+            for (light, &colour) in lights.iter().zip(colours) {
+                self.draw_light(&mut image, light, perceived(colour));
+            }
+        }
+
+        match &mut self.output {
+            Output::Gif(encoder, delay) => {
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, *delay))?;
+            }
+            Output::Pngs(dir, i) => {
+                image.save(dir.join(format!("frame_{i:04}.png")))?;
+                *i += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// A soft glow, switches being brighter and tighter than the underglow
+    /// which shines on whatever the keyboard is sat on
+    fn draw_light(&self, image: &mut RgbaImage, light: &Light, colour: [f32; 3]) {
+        let (radius, strength) = match light.kind {
+            Kind::Switch => (7.0, 1.0),
+            Kind::Underglow => (12.0, 0.6),
+        };
+        let radius = radius * self.scale as f32;
+
+        let (cx, cy) = self.to_pixel(light.location);
+        let x_range = (cx - radius).max(0.0) as u32..((cx + radius) as u32).min(image.width());
+        let y_range = (cy - radius).max(0.0) as u32..((cy + radius) as u32).min(image.height());
+
+        for y in y_range {
+            for x in x_range.clone() {
+                let d = ((x as f32 - cx).powi(2) + (y as f32 - cy).powi(2)).sqrt() / radius;
+                if d >= 1.0 {
+                    continue;
+                }
+
+                let a = strength * (1.0 - d * d);
+                let Rgba(p) = image.get_pixel_mut(x, y);
+                for (p, c) in p.iter_mut().zip(colour) {
+                    *p = (*p as f32 + c * a).min(255.0) as u8;
+                }
+            }
+        }
+    }
+}
+
+/// The leds are driven with linear values after gamma correction, screens
+/// expect them encoded
+fn perceived(colour: [u8; 3]) -> [f32; 3] {
+    colour.map(|c| 255.0 * (c as f32 / 255.0).powf(1.0 / 2.2))
+}
@@ -0,0 +1,18 @@
+//! The firmware's animations, built as they are for the desktop
+//!
+//! They expect to find `crate::keys`, `crate::rng`, `crate::side` and
+//! `crate::metrics`, which this crate provides stand-ins for. Not everything in
+//! them is used here.
+
+#![allow(dead_code)]
+
+#[path = "../../firmware/src/rgb/animation.rs"]
+pub mod animation;
+#[path = "../../firmware/src/rgb/animations/mod.rs"]
+pub mod animations;
+#[path = "../../firmware/src/rgb/blend.rs"]
+pub mod blend;
+#[path = "../../firmware/src/rgb/layout.rs"]
+pub mod layout;
+#[path = "../../firmware/src/rgb/math_utils.rs"]
+pub mod math_utils;
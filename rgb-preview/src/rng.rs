@@ -0,0 +1,41 @@
+//! Stands in for the firmware's hardware seeded rng, seeded from the command
+//! line instead so that a preview can be rendered again
+
+use std::cell::RefCell;
+
+use rand::{rngs::SmallRng, RngCore, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::seed_from_u64(0));
+}
+
+pub struct MyRng;
+
+pub fn seed(seed: u64) {
+    RNG.with(|r| *r.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
+pub fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+impl RngCore for MyRng {
+    fn next_u32(&mut self) -> u32 {
+        RNG.with(|r| r.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        RNG.with(|r| r.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        RNG.with(|r| r.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        RNG.with(|r| r.borrow_mut().try_fill_bytes(dest))
+    }
+}
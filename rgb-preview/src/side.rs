@@ -0,0 +1,24 @@
+//! Stands in for the firmware's idea of which side it's running on
+//!
+//! Both halves are simulated here, so the side is set around each call into
+//! an animation.
+
+use std::cell::Cell;
+
+use shared::side::KeyboardSide;
+
+thread_local! {
+    static SIDE: Cell<KeyboardSide> = const { Cell::new(KeyboardSide::Left) };
+}
+
+pub fn get_side() -> KeyboardSide {
+    SIDE.with(|s| s.get())
+}
+
+/// Run `f` as if on `side`
+pub fn as_side<T>(side: KeyboardSide, f: impl FnOnce() -> T) -> T {
+    let previous = SIDE.with(|s| s.replace(side));
+    let r = f();
+    SIDE.with(|s| s.set(previous));
+    r
+}
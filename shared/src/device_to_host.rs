@@ -1,7 +1,7 @@
 use core::hash::Hash;
 use serde::{Deserialize, Serialize};
 
-use crate::side::KeyboardSide;
+use crate::{script::ProgramError, side::KeyboardSide};
 
 pub const MAX_LOG_LEN: usize = 16;
 
@@ -15,5 +15,112 @@ pub struct DeviceToHost {
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DeviceToHostMsg {
-    Log { msg: heapless::Vec<u8, MAX_LOG_LEN> },
+    Log {
+        msg: heapless::Vec<u8, MAX_LOG_LEN>,
+    },
+    LinkMetrics(LinkMetrics),
+    FirmwareUpdate(FirmwareUpdateStatus),
+    /// How many times each key in a row of the matrix has been pressed, rows
+    /// 4 and 5 are chords
+    KeyCounts {
+        row: u8,
+        counts: [u32; 10],
+    },
+    ScriptUpload(ScriptUploadStatus),
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FirmwareUpdateStatus {
+    /// The update has started and chunks can be sent
+    Ready,
+    /// Everything before `upto` has been written
+    Written {
+        upto: u32,
+    },
+    /// The image checked out, the side is rebooting into it
+    Committed,
+    Failed(FirmwareUpdateError),
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FirmwareUpdateError {
+    /// This build has no bootloader to apply updates
+    Unsupported,
+    TooLarge,
+    NotStarted,
+    /// A chunk arrived that doesn't follow on from what was already written
+    OutOfOrder {
+        expected: u32,
+    },
+    Incomplete,
+    BadCrc,
+    Flash,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ScriptUploadStatus {
+    Ready,
+    /// Everything before `upto` has been received
+    Written {
+        upto: u16,
+    },
+    /// The program checked out and is stored, `hash` is its
+    /// [`script::hash`](crate::script::hash)
+    Installed {
+        hash: u32,
+    },
+    Failed(ScriptUploadError),
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ScriptUploadError {
+    TooLarge,
+    NotStarted,
+    /// A chunk arrived that doesn't follow on from what was already received
+    OutOfOrder {
+        expected: u16,
+    },
+    Incomplete,
+    Invalid(ProgramError),
+}
+
+/// Statistics about the link between the two halves, from the point of view of
+/// the sending side
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LinkMetrics {
+    pub link_up: bool,
+    /// The speed the link is currently running at
+    pub baud: u32,
+    /// Commands received that passed validation
+    pub received: u32,
+    /// Reliable commands that had to be resent after not being acked in time
+    pub retries: u32,
+    /// Commands that deserialized but failed their checksum
+    pub csum_failures: u32,
+    /// Frames that failed to deserialize
+    pub deser_errors: u32,
+    /// Frames that overflowed the receive buffer
+    pub overfull: u32,
+    /// Reliable commands that were given up on after too many retries
+    pub gave_up: u32,
+    /// Commands that never made it onto the link
+    pub dropped: DroppedMessages,
+}
+
+/// Counts of outgoing commands that were dropped (or merged into a newer one
+/// of the same kind) before being sent, by kind
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DroppedMessages {
+    pub keys: u32,
+    pub mouse: u32,
+    pub state_sync: u32,
+    pub animation: u32,
+    pub host: u32,
+    pub control: u32,
 }
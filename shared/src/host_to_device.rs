@@ -19,4 +19,309 @@ impl HostToDevice {
 
 #[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum HostToDeviceMsg {}
+pub enum HostToDeviceMsg {
+    FirmwareUpdate(FirmwareUpdate),
+    SetAnimation(AnimationSettings),
+    /// Set the led brightness (and turn them on if they were toggled off)
+    SetBrightness(u8),
+    /// Seconds without activity before the leds and display turn off, zero to
+    /// keep them on
+    SetIdleTimeout(u32),
+    /// Ask for the per key press counts, sent back as
+    /// [`KeyCounts`](crate::device_to_host::DeviceToHostMsg::KeyCounts)
+    RequestKeyCounts,
+    /// Show these colours instead of the animation, see [`DirectLeds`]
+    DirectLeds(DirectLeds),
+    SetPowerBudget(PowerBudget),
+    EditPlaylist(PlaylistEdit),
+    UploadScript(ScriptUpload),
+    Calibrate(CalibrationEdit),
+    /// Treat the host as running this OS rather than the one guessed when usb
+    /// came up, or go back to guessing with `None`. Remembered across reboots.
+    SetHostOs(Option<HostOs>),
+    EditSnippet(SnippetEdit),
+    /// The keyboard layout the host is set to, remembered across reboots
+    SetHostLayout(HostLayout),
+    /// How to type unicode when the host runs windows, remembered across
+    /// reboots
+    SetWindowsUnicode(WindowsUnicode),
+}
+
+pub const FIRMWARE_CHUNK_LEN: usize = 64;
+
+/// Steps of transferring a new firmware image to a side
+///
+/// Each step is answered with a
+/// [`FirmwareUpdateStatus`](crate::device_to_host::FirmwareUpdateStatus), the
+/// host should wait for it before sending the next step and resend if it
+/// doesn't turn up. Chunks must be sent in order, resending one that was
+/// already written is fine.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FirmwareUpdate {
+    /// Start a new update, `crc` is the crc32 of the whole image
+    Begin { len: u32, crc: u32 },
+    Chunk {
+        offset: u32,
+        data: heapless::Vec<u8, FIRMWARE_CHUNK_LEN>,
+    },
+    /// Check the image and reboot into it
+    Commit,
+}
+
+pub const SCRIPT_CHUNK_LEN: usize = 64;
+
+/// Steps of uploading an animation program, see [`crate::script`]
+///
+/// Works like a [`FirmwareUpdate`], each step is answered with a
+/// [`ScriptUploadStatus`](crate::device_to_host::ScriptUploadStatus). The
+/// program replaces any previous one and is kept across reboots. Upload it to
+/// both sides so they can both run it, then choose [`AnimationChoice::Script`]
+/// to show it.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ScriptUpload {
+    Begin {
+        len: u16,
+    },
+    Chunk {
+        offset: u16,
+        data: heapless::Vec<u8, SCRIPT_CHUNK_LEN>,
+    },
+    /// Check the program and start using it
+    Commit,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PerlinColours {
+    Random,
+    Single(Colour),
+    Double(Colour, Colour),
+}
+
+/// An animation and its parameters, colours left as `None` are picked at
+/// random for each splash or ripple
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AnimationChoice {
+    Off,
+    Snow,
+    Perlin(PerlinColours),
+    Rain {
+        colour: Option<Colour>,
+    },
+    Typing {
+        colour: Option<Colour>,
+    },
+    /// How often each key has been pressed
+    Heatmap,
+    /// The program uploaded with [`ScriptUpload`]
+    Script,
+}
+
+/// How much current the leds may draw (mA), remembered across reboots
+///
+/// The side plugged into usb powers the other through the trrs cable, so
+/// between them they should stay inside what the port can supply.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PowerBudget {
+    pub with_usb: u16,
+    pub without_usb: u16,
+}
+
+impl PowerBudget {
+    pub const DEFAULT: Self = Self {
+        with_usb: 300,
+        without_usb: 200,
+    };
+}
+
+pub const MAX_SNIPPETS: usize = 8;
+/// In bytes of utf-8
+pub const MAX_SNIPPET_LEN: usize = 96;
+pub const SNIPPET_CHUNK_LEN: usize = 64;
+
+/// Changes to the text typed by the snippet key actions, remembered across
+/// reboots
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SnippetEdit {
+    /// Empty the snippet at this index
+    Clear(u8),
+    /// Add text to the end of the snippet at this index, longer snippets are
+    /// sent as a few of these. Anything past [`MAX_SNIPPET_LEN`] is dropped.
+    Append(u8, heapless::String<SNIPPET_CHUNK_LEN>),
+    /// How long to wait after each keystroke when typing (ms), for hosts that
+    /// miss keys sent too quickly
+    SetKeystrokeDelay(u8),
+}
+
+/// For the things that differ between them, such as typing unicode
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostOs {
+    Linux,
+    Mac,
+    Windows,
+}
+
+/// Windows has no unicode entry that works everywhere out of the box
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WindowsUnicode {
+    /// Needs [WinCompose](https://github.com/samhocevar/wincompose) running,
+    /// with right alt as the compose key
+    #[default]
+    WinCompose,
+    /// Alt and numpad plus, needs `EnableHexNumpad` set in the registry
+    HexNumpad,
+}
+
+/// Keys are sent by position, so typing text depends on what the host makes of
+/// them. These are the PC (Windows and Linux) variants of each layout.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HostLayout {
+    #[default]
+    Us,
+    Uk,
+    /// German QWERTZ
+    De,
+    /// French AZERTY
+    Fr,
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LightKind {
+    Switch,
+    Underglow,
+}
+
+/// Changes to how the leds are corrected, remembered across reboots
+///
+/// Leds from different batches, or under a different case, need different
+/// corrections. Each side keeps its own calibration, so set `target_side` when
+/// correcting particular lights.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CalibrationEdit {
+    /// Go back to the defaults
+    Reset,
+    /// Scale each channel of every light of a kind, 255 leaves it as it is
+    SetKind(LightKind, Colour),
+    /// Use this correction for the light at an index rather than the one for
+    /// its kind, or go back to that with `None`
+    SetLight(u8, Option<Colour>),
+    /// The gamma exponent, in tenths
+    SetGamma(u8),
+    /// Show a pattern instead of the animation to calibrate by, or go back to
+    /// the animation with `None`. This one isn't remembered.
+    ShowPattern(Option<TestPattern>),
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TestPattern {
+    /// Every light the same colour, to match their corrections up by
+    Solid(Colour),
+    /// Greys stepping up from off to full through the lights, to judge the
+    /// gamma by
+    Ramp,
+}
+
+pub const DIRECT_LEDS_CHUNK_LEN: usize = 18;
+
+/// Part of a frame of led colours streamed from the host
+///
+/// Each side has its own leds, so set `target_side` on the message. Once
+/// frames are being sent they're shown instead of the animation, which takes
+/// back over if frames stop for a couple of seconds.
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DirectLeds {
+    /// Index of the first led `colours` is for
+    pub offset: u8,
+    pub colours: heapless::Vec<Colour, DIRECT_LEDS_CHUNK_LEN>,
+}
+
+/// Which animation to show, these are remembered across reboots
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnimationSettings {
+    /// Switch to this animation now and show it at boot, if `None` one is
+    /// picked at random
+    pub animation: Option<AnimationChoice>,
+    /// Whether to keep switching to a random animation every few minutes
+    pub randomise: bool,
+}
+
+impl Default for AnimationSettings {
+    fn default() -> Self {
+        Self {
+            animation: None,
+            randomise: true,
+        }
+    }
+}
+
+pub const MAX_PLAYLIST_LEN: usize = 16;
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PlaylistEntry {
+    pub animation: AnimationChoice,
+    /// How long to show the animation for before moving on (seconds)
+    pub secs: u32,
+}
+
+/// Animations to cycle through, remembered across reboots
+///
+/// When there are entries this takes over from
+/// [`AnimationSettings::randomise`].
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Playlist {
+    pub entries: heapless::Vec<PlaylistEntry, MAX_PLAYLIST_LEN>,
+    /// Go through the entries in a random order rather than as listed
+    pub shuffle: bool,
+    /// Stay on the entry at this index rather than moving through the list
+    pub pinned: Option<u8>,
+}
+
+impl Playlist {
+    pub const EMPTY: Self = Self {
+        entries: heapless::Vec::new(),
+        shuffle: false,
+        pinned: None,
+    };
+
+    pub fn pinned_entry(&self) -> Option<&PlaylistEntry> {
+        self.entries.get(self.pinned? as usize)
+    }
+}
+
+/// Changes to the [`Playlist`], which is too big to send in one message
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PlaylistEdit {
+    Clear,
+    /// Add an entry to the end, ignored if the playlist is full
+    Push(PlaylistEntry),
+    /// Remove the entry at this index
+    Remove(u8),
+    SetShuffle(bool),
+    /// Stay on the entry at this index, switching to it straight away, or
+    /// start moving through the list again
+    Pin(Option<u8>),
+}
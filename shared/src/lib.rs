@@ -4,4 +4,5 @@ pub mod cmd;
 pub mod device_to_host;
 pub mod hid;
 pub mod host_to_device;
+pub mod script;
 pub mod side;
@@ -0,0 +1,216 @@
+//! Bytecode for led animations written on the host
+//!
+//! A program is run for every light on each tick of the animation. It's a
+//! stack machine working on I16F16 numbers (the four bytes of a constant are
+//! the raw bits, little endian). The stack starts empty and the program has to
+//! finish with exactly the red, green and blue of the light on it, each from 0
+//! to 1 with blue on top.
+//!
+//! The first byte of a program is its tick rate in Hz, the rest are ops.
+
+use core::hash::Hash;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_PROGRAM_LEN: usize = 256;
+pub const MAX_STACK: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Op {
+    /// Push the four bytes that follow
+    Const,
+
+    /// Seconds since the animation started, wraps every hour
+    Time,
+    /// Location of the light (mm), see the firmware's `rgb::layout`
+    X,
+    Y,
+    /// Index of the light on its side
+    Index,
+    /// 1 for lights under switches, 0 for underglow
+    IsSwitch,
+    /// 1 if the key under this light is held
+    Pressed,
+    /// Seconds since the key under this light was last pressed, very large if
+    /// it never has been
+    SincePress,
+
+    // pop b, pop a, push (a op b)
+    Add,
+    Sub,
+    Mul,
+    /// Division by zero gives zero
+    Div,
+    Rem,
+    Min,
+    Max,
+    /// 1 if a < b, otherwise 0
+    Lt,
+    /// 1 if a > b, otherwise 0
+    Gt,
+
+    // pop a, push f(a)
+    Neg,
+    Abs,
+    Floor,
+    Fract,
+    /// sin(2πa), so a full wave every 1
+    Sin,
+    /// A pseudo random number from 0 to 1, the same for the same a
+    Hash,
+
+    /// Pop a hue (0 to 1 round the colour wheel) and push its red, green and
+    /// blue
+    Hue,
+    /// Pop c, pop b, pop a, push b if a is nonzero, otherwise c
+    Select,
+
+    Dup,
+    Swap,
+    Drop,
+}
+
+impl Op {
+    /// In the order of their values
+    const ALL: [Op; 28] = [
+        Op::Const,
+        Op::Time,
+        Op::X,
+        Op::Y,
+        Op::Index,
+        Op::IsSwitch,
+        Op::Pressed,
+        Op::SincePress,
+        Op::Add,
+        Op::Sub,
+        Op::Mul,
+        Op::Div,
+        Op::Rem,
+        Op::Min,
+        Op::Max,
+        Op::Lt,
+        Op::Gt,
+        Op::Neg,
+        Op::Abs,
+        Op::Floor,
+        Op::Fract,
+        Op::Sin,
+        Op::Hash,
+        Op::Hue,
+        Op::Select,
+        Op::Dup,
+        Op::Swap,
+        Op::Drop,
+    ];
+
+    pub fn from_u8(b: u8) -> Option<Self> {
+        Self::ALL.get(b as usize).copied()
+    }
+
+    /// Bytes following the op
+    pub fn immediate_len(self) -> usize {
+        match self {
+            Op::Const => 4,
+            _ => 0,
+        }
+    }
+
+    /// How many values the op pops and pushes
+    pub fn stack_effect(self) -> (usize, usize) {
+        match self {
+            Op::Const
+            | Op::Time
+            | Op::X
+            | Op::Y
+            | Op::Index
+            | Op::IsSwitch
+            | Op::Pressed
+            | Op::SincePress => (0, 1),
+            Op::Add
+            | Op::Sub
+            | Op::Mul
+            | Op::Div
+            | Op::Rem
+            | Op::Min
+            | Op::Max
+            | Op::Lt
+            | Op::Gt => (2, 1),
+            Op::Neg | Op::Abs | Op::Floor | Op::Fract | Op::Sin | Op::Hash => (1, 1),
+            Op::Hue => (1, 3),
+            Op::Select => (3, 1),
+            Op::Dup => (1, 2),
+            Op::Swap => (2, 2),
+            Op::Drop => (1, 0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Eq, PartialEq, Hash, Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProgramError {
+    Empty,
+    TooLong,
+    ZeroTickRate,
+    /// `at` is the offset of the op within the program
+    UnknownOp {
+        at: u16,
+    },
+    Truncated {
+        at: u16,
+    },
+    StackUnderflow {
+        at: u16,
+    },
+    StackOverflow {
+        at: u16,
+    },
+    /// The program didn't finish with a colour on the stack
+    NotAColour,
+}
+
+/// Check that a program can be run, so that running it never has to
+pub fn validate(program: &[u8]) -> Result<(), ProgramError> {
+    let (&tick_rate, ops) = program.split_first().ok_or(ProgramError::Empty)?;
+
+    if program.len() > MAX_PROGRAM_LEN {
+        return Err(ProgramError::TooLong);
+    }
+
+    if tick_rate == 0 {
+        return Err(ProgramError::ZeroTickRate);
+    }
+
+    let mut depth: usize = 0;
+    let mut i = 0;
+
+    while i < ops.len() {
+        let at = (i + 1) as u16;
+        let op = Op::from_u8(ops[i]).ok_or(ProgramError::UnknownOp { at })?;
+
+        i += 1 + op.immediate_len();
+        if i > ops.len() {
+            return Err(ProgramError::Truncated { at });
+        }
+
+        let (pops, pushes) = op.stack_effect();
+        depth = depth
+            .checked_sub(pops)
+            .ok_or(ProgramError::StackUnderflow { at })?
+            + pushes;
+
+        if depth > MAX_STACK {
+            return Err(ProgramError::StackOverflow { at });
+        }
+    }
+
+    if depth != 3 {
+        return Err(ProgramError::NotAColour);
+    }
+
+    Ok(())
+}
+
+/// Identifies a program, so both sides can check they're running the same one
+pub fn hash(program: &[u8]) -> u32 {
+    crc32fast::hash(program)
+}